@@ -0,0 +1,228 @@
+use crate::digest::{self, Window};
+use crate::filter::Mode;
+use crate::output::Format;
+
+use argh::FromArgs;
+
+use std::path::PathBuf;
+
+/// Follow new posts on the Hive Workshop
+#[derive(Debug, FromArgs)]
+pub struct Cli {
+    /// root URL of the forum, e.g. a local mirror or mock server; overrides
+    /// the config file
+    #[argh(option)]
+    pub base_url: Option<String>,
+    /// how many pages of new posts to crawl per refresh, at most
+    #[argh(option, default = "3", from_str_fn(page_count))]
+    pub pages: usize,
+    /// where to keep the history of seen threads, defaults to the user data
+    /// directory
+    #[argh(option)]
+    pub history: Option<PathBuf>,
+    /// path of the config file, defaults to the user config directory
+    #[argh(option)]
+    pub config: Option<PathBuf>,
+    #[argh(subcommand)]
+    pub command: Option<Command>,
+
+    // The flags from before there were subcommands, kept working but no
+    // longer shown in --help.
+    /// same as `watch`
+    #[argh(switch, hidden_help)]
+    pub headless: bool,
+    /// same as `list`
+    #[argh(switch, hidden_help)]
+    pub once: bool,
+    /// output format of --headless and --once
+    #[argh(option, hidden_help)]
+    pub format: Option<Format>,
+    /// shorthand for --format json
+    #[argh(switch, hidden_help)]
+    pub json: bool,
+}
+
+impl Cli {
+    /// What to run when no subcommand was given: `--once` and `--headless`
+    /// stand for `list` and `watch`, and the terminal UI is the default.
+    pub fn default_command(&self) -> Command {
+        let format = if self.json {
+            Format::Json
+        } else {
+            self.format.unwrap_or(Format::Text)
+        };
+        if self.once {
+            Command::List(List { format })
+        } else if self.headless {
+            Command::Watch(Watch { format })
+        } else {
+            Command::Tui(Tui::default())
+        }
+    }
+}
+
+#[derive(Debug, FromArgs)]
+#[argh(subcommand)]
+pub enum Command {
+    Tui(Tui),
+    Watch(Watch),
+    List(List),
+    Export(Export),
+    Filters(Filters),
+    History(History),
+    Digest(Digest),
+}
+
+fn page_count(value: &str) -> Result<usize, String> {
+    match value.parse() {
+        Ok(pages) if pages > 0 => Ok(pages),
+        _ => Err(format!("expected at least 1 page, got {:?}", value)),
+    }
+}
+
+/// Show the terminal UI (the default).
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "tui")]
+pub struct Tui {
+    /// time in ms between two ticks.
+    #[argh(option, default = "250")]
+    pub tick_rate: u64,
+    /// whether unicode symbols are used to improve the overall look of the app
+    #[argh(option, default = "true")]
+    pub enhanced_graphics: bool,
+}
+
+impl Default for Tui {
+    fn default() -> Tui {
+        Tui {
+            tick_rate: 250,
+            enhanced_graphics: true,
+        }
+    }
+}
+
+/// Keep refreshing and print each new thread that passes the filters.
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "watch")]
+pub struct Watch {
+    /// output format: text, json (one object per line) or csv
+    #[argh(option, default = "Format::Text")]
+    pub format: Format,
+}
+
+/// Fetch once, print the threads that pass the filters and exit with 0, with 1
+/// if the fetch failed, or with 2 if nothing was new.
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "list")]
+pub struct List {
+    /// output format: text, json or csv
+    #[argh(option, default = "Format::Text")]
+    pub format: Format,
+}
+
+/// Write every thread in the history.
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "export")]
+pub struct Export {
+    /// output format: text, json or csv
+    #[argh(option, default = "Format::Json")]
+    pub format: Format,
+    /// file to write to instead of stdout
+    #[argh(option, short = 'o')]
+    pub output: Option<PathBuf>,
+}
+
+/// Inspect or change the filters in the config file.
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "filters")]
+pub struct Filters {
+    #[argh(subcommand)]
+    pub command: FiltersCommand,
+}
+
+#[derive(Debug, FromArgs)]
+#[argh(subcommand)]
+pub enum FiltersCommand {
+    Add(FiltersAdd),
+    Remove(FiltersRemove),
+    List(FiltersList),
+    Mode(FiltersMode),
+}
+
+/// Add a forum or title rule, or change an existing one.
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "add")]
+pub struct FiltersAdd {
+    /// forum name, or title pattern with --title
+    #[argh(positional)]
+    pub pattern: String,
+    /// add a title rule instead of a forum
+    #[argh(switch)]
+    pub title: bool,
+    /// allow the forum, or include matching titles, instead of blocking them
+    #[argh(switch)]
+    pub allow: bool,
+    /// treat the title pattern as a regex rather than a keyword
+    #[argh(switch)]
+    pub regex: bool,
+}
+
+/// Remove a forum or title rule.
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "remove")]
+pub struct FiltersRemove {
+    /// forum name, or title pattern with --title
+    #[argh(positional)]
+    pub pattern: String,
+    /// remove a title rule instead of a forum
+    #[argh(switch)]
+    pub title: bool,
+}
+
+/// Print the filters.
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "list")]
+pub struct FiltersList {}
+
+/// Switch between blocklist and allowlist mode.
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "mode")]
+pub struct FiltersMode {
+    /// blocklist or allowlist
+    #[argh(positional)]
+    pub mode: Mode,
+}
+
+/// Print the threads seen so far.
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "history")]
+pub struct History {
+    /// only print unread threads
+    #[argh(switch)]
+    pub unread: bool,
+    /// mark every thread as read
+    #[argh(switch)]
+    pub mark_read: bool,
+}
+
+/// Collect the new threads that pass the filters, and send them as one digest
+/// each time the window closes.
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "digest")]
+pub struct Digest {
+    /// how long to collect for: hourly, daily or a number of seconds;
+    /// overrides the config file
+    #[argh(option)]
+    pub window: Option<Window>,
+    /// digest format: text, markdown or html; overrides the config file
+    #[argh(option)]
+    pub format: Option<digest::Format>,
+    /// file to write each digest to instead of stdout, where {{time}} stands for
+    /// the end of its window; overrides the config file
+    #[argh(option, short = 'o')]
+    pub output: Option<PathBuf>,
+    /// refresh once, send whatever is queued and exit, with 1 if the refresh
+    /// failed
+    #[argh(switch)]
+    pub now: bool,
+}
@@ -0,0 +1,124 @@
+use crate::cli;
+use crate::config::Config;
+use crate::feed;
+use crate::filter::{Action, Matcher, Status, TitleRule};
+use crate::history::{Entry, History};
+use crate::output::{self, Format};
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, Write};
+
+pub fn export(args: cli::Export, history: &History, base_url: &str) -> Result<(), Box<dyn Error>> {
+    let mut out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout()),
+    };
+
+    // JSON keeps everything we know, so it can be read back as a history.
+    if args.format == Format::Json {
+        let entries: Vec<&Entry> = history.entries().collect();
+        writeln!(out, "{}", serde_json::to_string_pretty(&entries)?)?;
+        return Ok(());
+    }
+
+    let data = history.entries().map(|entry| &entry.datum);
+    output::write_all(&mut out, args.format, data, base_url)
+}
+
+pub fn filters(args: cli::Filters, mut config: Config) -> Result<(), Box<dyn Error>> {
+    let filters = &mut config.filters;
+    match args.command {
+        cli::FiltersCommand::Add(add) if add.title => {
+            let rule = TitleRule::new(
+                add.pattern,
+                if add.regex {
+                    Matcher::Regex
+                } else {
+                    Matcher::Keyword
+                },
+                if add.allow {
+                    Action::Include
+                } else {
+                    Action::Exclude
+                },
+            );
+            rule.validate()?;
+            filters.set_title_rule(rule);
+        }
+        cli::FiltersCommand::Add(add) => {
+            let status = if add.allow {
+                Status::Allowed
+            } else {
+                Status::Blocked
+            };
+            filters.set_forum(add.pattern, status);
+        }
+        cli::FiltersCommand::Remove(remove) => {
+            let before = filters.forums.len() + filters.titles.len();
+            if remove.title {
+                filters.titles.retain(|rule| rule.pattern != remove.pattern);
+            } else {
+                filters
+                    .forums
+                    .retain(|filter| filter.forum != remove.pattern);
+            }
+            if filters.forums.len() + filters.titles.len() == before {
+                return Err(format!("no filter matches {:?}", remove.pattern).into());
+            }
+        }
+        cli::FiltersCommand::Mode(mode) => filters.mode = mode.mode,
+        cli::FiltersCommand::List(_) => {
+            println!(
+                "Mode: {} (unknown forums: {})",
+                filters.mode.label(),
+                filters.unknown_forums.label()
+            );
+            println!("Forums:");
+            for filter in &filters.forums {
+                println!("  {:8} {}", filter.status.label(), filter.forum);
+            }
+            println!("Title rules:");
+            for rule in &filters.titles {
+                println!(
+                    "  {:8} {:8} {}",
+                    rule.action.label(),
+                    rule.matcher.label(),
+                    rule.pattern
+                );
+            }
+            return Ok(());
+        }
+    }
+    config.save()?;
+    Ok(())
+}
+
+pub fn history(
+    args: cli::History,
+    mut history: History,
+    base_url: &str,
+) -> Result<(), Box<dyn Error>> {
+    if args.mark_read {
+        history.mark_all_read();
+        history.save()?;
+        return Ok(());
+    }
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for entry in history
+        .entries()
+        .filter(|entry| !args.unread || !entry.read)
+    {
+        writeln!(
+            out,
+            "{} {} :: {} :: {}",
+            if entry.read { " " } else { "*" },
+            entry.datum.forum,
+            entry.datum.title,
+            feed::url(base_url, &entry.datum.href)
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,281 @@
+use crate::demo::ThwDatum;
+use crate::digest::{self, Smtp, Window};
+use crate::filter::Filters;
+use crate::notify::{self, Priority, PriorityRule, QuietHours, Webhook};
+use crate::sort::SortOrder;
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use tui::style::Color;
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Longest `refresh_interval` honoured: a day.
+const MAX_REFRESH_INTERVAL: u64 = 24 * 60 * 60;
+
+/// Settings read from `config.toml`. Anything missing from the file falls back
+/// to its default.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub base_url: String,
+    /// Seconds between two refreshes, at least 1 and at most a day.
+    pub refresh_interval: u64,
+    /// Command that threads are opened with, followed by their URL. Defaults
+    /// to the platform's usual opener, e.g. `xdg-open`.
+    pub opener: Option<String>,
+    pub sort: SortOrder,
+    /// Whether the selection starts out following the newest thread.
+    pub follow: bool,
+    pub keys: Keys,
+    pub colors: Colors,
+    pub filters: Filters,
+    pub notify: Notify,
+    pub alerts: Alerts,
+    pub digest: Digest,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Keys {
+    pub quit: char,
+    pub copy: char,
+    pub open: char,
+    pub next_unread: char,
+    pub mark_all_read: char,
+    pub show_hidden: char,
+    pub cycle_sort: char,
+    pub toggle_follow: char,
+    pub toggle_preview: char,
+    pub toggle_filter: char,
+    pub add_filter: char,
+    pub delete_filter: char,
+    pub toggle_regex: char,
+    pub toggle_mode: char,
+}
+
+/// What to do about new threads that pass the filters, besides listing them.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Notify {
+    /// Command to run for each of them; see `notify::CommandNotifier`.
+    pub command: Option<String>,
+    /// Where webhook deliveries are logged, defaults to the user data
+    /// directory.
+    pub webhook_log: Option<PathBuf>,
+    /// Local times during which only high priority threads alert; the rest
+    /// are queued for the next digest.
+    pub quiet_hours: Vec<QuietHours>,
+    /// A thread gets the highest priority of the rules it matches, or normal
+    /// priority if none.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<PriorityRule>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub webhooks: Vec<Webhook>,
+}
+
+impl Notify {
+    pub fn priority(&self, datum: &ThwDatum) -> Priority {
+        self.rules
+            .iter()
+            .filter(|rule| rule.matches(datum))
+            .map(|rule| rule.priority)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Whether a new `datum` should alert now, rather than wait for the next
+    /// digest.
+    pub fn alerts_now(&self, datum: &ThwDatum) -> bool {
+        let now = Local::now().time();
+        match self.priority(datum) {
+            Priority::Low => false,
+            Priority::Normal => !self.quiet_hours.iter().any(|quiet| quiet.contains(now)),
+            Priority::High => true,
+        }
+    }
+}
+
+/// How the terminal UI draws attention to new threads. Everything is off by
+/// default.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Alerts {
+    /// Ring the terminal bell.
+    pub bell: bool,
+    /// Flash the border of the topic list.
+    pub flash: bool,
+    /// Show the number of unread threads in the terminal's title.
+    pub title: bool,
+    /// Which new threads ring or flash; any that pass the filters if empty.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<AlertRule>,
+}
+
+/// Matches threads in `forum` whose title contains `keyword`, ignoring case.
+/// Either can be left out.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AlertRule {
+    pub forum: Option<String>,
+    pub keyword: Option<String>,
+}
+
+impl Alerts {
+    /// Whether a new `datum` should ring or flash.
+    pub fn matches(&self, datum: &ThwDatum) -> bool {
+        self.rules.is_empty()
+            || self.rules.iter().any(|rule| {
+                notify::thread_matches(rule.forum.as_deref(), rule.keyword.as_deref(), datum)
+            })
+    }
+}
+
+/// Settings for the `digest` subcommand.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Digest {
+    pub window: Window,
+    pub format: digest::Format,
+    /// File to write each digest to instead of stdout. `{time}` is replaced
+    /// with the end of the digest's window.
+    pub output: Option<PathBuf>,
+    /// Mail each digest instead of printing it.
+    pub smtp: Option<Smtp>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Colors {
+    pub tabs: Color,
+    pub selected_tab: Color,
+    pub selection: Color,
+    pub blocked: Color,
+    pub allowed: Color,
+    /// The marker on threads with new replies.
+    pub updated: Color,
+    /// The topic list's border while it flashes.
+    pub flash: Color,
+    pub error: Color,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            base_url: "https://www.hiveworkshop.com/".into(),
+            refresh_interval: 60,
+            opener: None,
+            sort: SortOrder::default(),
+            follow: false,
+            keys: Keys::default(),
+            colors: Colors::default(),
+            filters: Filters::blocking(vec![
+                "Maps",
+                "Models",
+                "Site Discussion",
+                "Multiplayer LFG",
+                "Skins",
+                "Something Else",
+                "Medivh's Tower",
+                "Modeling & Animation",
+                "Requests",
+                "The Legends of Arkain Series",
+                "Off-Topic",
+                "Map Development",
+                "Spells",
+                "Warcraft: Ultimate Battle",
+                "Icons",
+                "Art and Graphic Designs",
+            ]),
+            notify: Notify::default(),
+            alerts: Alerts::default(),
+            digest: Digest::default(),
+            path: None,
+        }
+    }
+}
+
+impl Default for Keys {
+    fn default() -> Keys {
+        Keys {
+            quit: 'q',
+            copy: 'c',
+            open: 'o',
+            next_unread: 'n',
+            mark_all_read: 'a',
+            show_hidden: 'h',
+            cycle_sort: 's',
+            toggle_follow: 'f',
+            toggle_preview: 'p',
+            toggle_filter: ' ',
+            add_filter: 'a',
+            delete_filter: 'd',
+            toggle_regex: 'r',
+            toggle_mode: 'm',
+        }
+    }
+}
+
+impl Default for Colors {
+    fn default() -> Colors {
+        Colors {
+            tabs: Color::Green,
+            selected_tab: Color::Yellow,
+            selection: Color::Yellow,
+            blocked: Color::Red,
+            allowed: Color::Green,
+            updated: Color::Cyan,
+            flash: Color::LightYellow,
+            error: Color::Magenta,
+        }
+    }
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/thw-subscriber/config.toml` or the platform
+    /// equivalent.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("thw-subscriber").join("config.toml"))
+    }
+
+    /// Reads the config at `path`, which doesn't have to exist yet.
+    pub fn load(path: PathBuf) -> io::Result<Config> {
+        let mut config: Config = match fs::read_to_string(&path) {
+            Ok(text) => {
+                toml::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Config::default(),
+            Err(e) => return Err(e),
+        };
+        if config.refresh_interval == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "refresh_interval must be at least 1 second",
+            ));
+        }
+        config.refresh_interval = config.refresh_interval.min(MAX_REFRESH_INTERVAL);
+        for rule in &config.filters.titles {
+            rule.validate()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        }
+        config.path = Some(path);
+        Ok(config)
+    }
+
+    /// Writes the config back to where it was loaded from, if anywhere.
+    pub fn save(&self) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, text)
+    }
+}
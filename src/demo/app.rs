@@ -1,9 +1,30 @@
+use crate::config::Config;
+use crate::digest::Queue;
+use crate::feed::{self, FeedError, Post};
+use crate::filter::{Action, Matcher, TitleRule};
+use crate::history::{History, Sighting};
+use crate::notify::{self, Notifier};
 use crate::util::{RandomSignal, SinSignal, StatefulList, TabsState};
 
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Add;
+use std::process::{Command, Stdio};
 use std::sync::mpsc;
+use std::thread;
 use std::time::{Duration, Instant};
 
 use arboard::Clipboard;
+use crossterm::event::KeyCode;
+use indexmap::IndexSet;
+use serde::{Deserialize, Serialize};
+use tui::widgets::TableState;
+
+/// How many errors the footer has room for.
+const MAX_ERRORS: usize = 5;
+/// How long the topic list flashes for after an alert.
+const FLASH_DURATION: Duration = Duration::from_secs(2);
 
 pub struct Signal<S: Iterator> {
     source: S,
@@ -46,11 +67,123 @@ pub struct Server<'a> {
     pub status: &'a str,
 }
 
-#[derive(Debug, Eq, PartialEq, Hash)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ThwDatum {
     pub title: String,
     pub forum: String,
     pub href: String,
+    /// The number at the end of the thread's slug, e.g. 123 in
+    /// `threads/some-title.123/`.
+    #[serde(default)]
+    pub thread_id: Option<u64>,
+    #[serde(default)]
+    pub starter: Option<String>,
+    #[serde(default)]
+    pub last_poster: Option<String>,
+    /// Unix time of the latest post, in seconds.
+    #[serde(default)]
+    pub last_post_at: Option<u64>,
+    #[serde(default)]
+    pub replies: Option<u64>,
+    #[serde(default)]
+    pub views: Option<u64>,
+}
+
+impl ThwDatum {
+    /// What identifies the thread however it's renamed or moved: its id, or
+    /// its href for the odd thread without one.
+    pub fn key(&self) -> String {
+        match self.thread_id {
+            Some(id) => id.to_string(),
+            None => self.href.clone(),
+        }
+    }
+
+    /// Whether this sighting of the thread shows posts that `earlier` didn't.
+    pub fn has_replies_since(&self, earlier: &ThwDatum) -> bool {
+        match (self.last_post_at, earlier.last_post_at) {
+            (Some(now), Some(then)) => now > then,
+            _ => matches!((self.replies, earlier.replies), (Some(now), Some(then)) if now > then),
+        }
+    }
+}
+
+#[cfg(test)]
+impl ThwDatum {
+    /// A thread with nothing known beyond its title, forum and href.
+    pub fn bare(title: &str, forum: &str, href: &str) -> ThwDatum {
+        ThwDatum {
+            title: title.into(),
+            forum: forum.into(),
+            href: href.into(),
+            thread_id: crate::feed::thread_id(href),
+            starter: None,
+            last_poster: None,
+            last_post_at: None,
+            replies: None,
+            views: None,
+        }
+    }
+}
+
+// Two sightings of a thread are the same thread, whatever changed in between.
+impl PartialEq for ThwDatum {
+    fn eq(&self, other: &ThwDatum) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for ThwDatum {}
+
+impl Hash for ThwDatum {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+/// What the preview pane knows about a thread's latest post.
+pub enum Preview {
+    Loading,
+    Ready(Post),
+    Failed(String),
+}
+
+/// What a refresh brought in, for the header.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RefreshSummary {
+    pub new: usize,
+    pub updated: usize,
+    /// New or updated threads that the filters hide.
+    pub hidden: usize,
+}
+
+impl Add for RefreshSummary {
+    type Output = RefreshSummary;
+
+    fn add(self, other: RefreshSummary) -> RefreshSummary {
+        RefreshSummary {
+            new: self.new + other.new,
+            updated: self.updated + other.updated,
+            hidden: self.hidden + other.hidden,
+        }
+    }
+}
+
+impl fmt::Display for RefreshSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "+{} new, {} updated, {} hidden",
+            self.new, self.updated, self.hidden
+        )
+    }
+}
+
+/// Which of the tables on the Filters tab has focus.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FilterPane {
+    Forums,
+    Titles,
 }
 
 pub struct App<'a> {
@@ -59,26 +192,65 @@ pub struct App<'a> {
     pub tabs: TabsState<'a>,
     pub show_chart: bool,
     pub next_update: Instant, // seconds
+    pub refresh_interval: Duration,
     pub progress: f64,
     pub refresh_sender: mpsc::Sender<()>,
-    pub results_receiver: mpsc::Receiver<ThwDatum>,
+    pub results_receiver: mpsc::Receiver<Result<Vec<ThwDatum>, FeedError>>,
+    /// What the latest refresh brought in, once there's been one.
+    pub last_refresh: Option<RefreshSummary>,
     pub sparkline: Signal<RandomSignal>,
+    /// Every thread received so far, whether the filters allow it or not.
+    pub threads: IndexSet<ThwDatum>,
+    /// The threads currently on display; see `refresh_view`.
     pub tasks: StatefulList<ThwDatum>,
+    /// How many threads the filters are hiding.
+    pub hidden: usize,
+    pub show_hidden: bool,
+    /// Whether the selection sticks to the newest thread as others arrive.
+    pub follow: bool,
     pub logs: StatefulList<(&'a str, &'a str)>,
     pub signals: Signals,
     pub barchart: Vec<(&'a str, u64)>,
     pub servers: Vec<Server<'a>>,
     pub enhanced_graphics: bool,
+    pub base_url: String,
     pub errors: Vec<String>,
-    pub filters: Vec<String>,
+    pub config: Config,
+    pub filter_pane: FilterPane,
+    pub filter_state: TableState,
+    pub title_rule_state: TableState,
+    /// Text typed so far into the "add filter" box, while it's open.
+    pub input: Option<String>,
+    pub history: History,
+    /// New threads held back for the next digest; see `Notify::alerts_now`.
+    pub queue: Queue,
+    pub notifiers: Vec<Box<dyn Notifier>>,
+    /// Set when a new thread should ring the bell, until it's been rung.
+    pub ring_bell: bool,
+    pub flash_until: Option<Instant>,
+    pub show_preview: bool,
+    /// Latest posts fetched so far, by href.
+    pub previews: HashMap<String, Preview>,
+    /// The href the preview pane was last showing, so that a failed preview
+    /// is retried when its thread is selected again.
+    previewed: Option<String>,
+    pub preview_sender: mpsc::Sender<String>,
+    pub preview_receiver: mpsc::Receiver<(String, Result<Post, FeedError>)>,
 }
 
 impl<'a> App<'a> {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         title: &'a str,
         enhanced_graphics: bool,
+        base_url: String,
+        config: Config,
         refresh_sender: mpsc::Sender<()>,
-        results_receiver: mpsc::Receiver<ThwDatum>,
+        results_receiver: mpsc::Receiver<Result<Vec<ThwDatum>, FeedError>>,
+        history: History,
+        queue: Queue,
+        preview_sender: mpsc::Sender<String>,
+        preview_receiver: mpsc::Receiver<(String, Result<Post, FeedError>)>,
     ) -> App<'a> {
         let mut rand_signal = RandomSignal::new(0, 100);
         let sparkline_points = rand_signal.by_ref().take(300).collect();
@@ -86,21 +258,29 @@ impl<'a> App<'a> {
         let sin1_points = sin_signal.by_ref().take(100).collect();
         let mut sin_signal2 = SinSignal::new(0.1, 2.0, 10.0);
         let sin2_points = sin_signal2.by_ref().take(200).collect();
-        App {
+        let refresh_interval = Duration::from_secs(config.refresh_interval);
+        let notifiers = notify::from_config(&config);
+        let mut app = App {
             title,
             should_quit: false,
             tabs: TabsState::new(vec!["New posts", "Filters"]),
             show_chart: true,
-            next_update: Instant::now() + Duration::from_secs(60),
+            next_update: Instant::now() + refresh_interval,
+            refresh_interval,
             progress: 0.0,
             refresh_sender,
             results_receiver,
+            last_refresh: None,
             sparkline: Signal {
                 source: rand_signal,
                 points: sparkline_points,
                 tick_rate: 1,
             },
+            threads: IndexSet::new(),
             tasks: StatefulList::new(),
+            hidden: 0,
+            show_hidden: false,
+            follow: config.follow,
             logs: StatefulList::new(),
 
             signals: Signals {
@@ -144,33 +324,47 @@ impl<'a> App<'a> {
                 },
             ],
             enhanced_graphics,
+            base_url,
             errors: vec![],
-            filters: vec![
-                "Maps",
-                "Models",
-                "Site Discussion",
-                "Multiplayer LFG",
-                "Skins",
-                "Something Else",
-                "Medivh's Tower",
-                "Modeling & Animation",
-                "Requests",
-                "The Legends of Arkain Series",
-                "Off-Topic",
-                "Map Development",
-                "Spells",
-                "Warcraft: Ultimate Battle",
-                "Icons",
-                "Art and Graphic Designs",
-            ]
-            .into_iter()
-            .map(|s| s.into())
-            .collect(),
-        }
+            config,
+            filter_pane: FilterPane::Forums,
+            filter_state: TableState::default(),
+            title_rule_state: TableState::default(),
+            input: None,
+            history,
+            queue,
+            notifiers,
+            ring_bell: false,
+            flash_until: None,
+            show_preview: true,
+            previews: HashMap::new(),
+            previewed: None,
+            preview_sender,
+            preview_receiver,
+        };
+
+        // Pick up where the last session left off.
+        let seen: Vec<ThwDatum> = app
+            .history
+            .entries()
+            .map(|entry| entry.datum.clone())
+            .collect();
+        app.threads.extend(seen);
+        app.refresh_view();
+
+        app
     }
 
     pub fn on_up(&mut self) {
-        self.tasks.previous();
+        match self.tabs.index {
+            0 => {
+                self.follow = false;
+                self.tasks.previous();
+                self.mark_selected_read();
+            }
+            1 => self.select_filter(|idx, len| (idx + len - 1) % len),
+            _ => {}
+        }
     }
 
     pub fn get_uri(&mut self) -> Option<String> {
@@ -179,12 +373,259 @@ impl<'a> App<'a> {
                 .items
                 .iter()
                 .nth(idx)
-                .map(|thw| format!("https://www.hiveworkshop.com/{}", thw.href))
+                .map(|thw| feed::url(&self.base_url, &thw.href))
         })
     }
 
     pub fn on_down(&mut self) {
-        self.tasks.next();
+        match self.tabs.index {
+            0 => {
+                self.follow = false;
+                self.tasks.next();
+                self.mark_selected_read();
+            }
+            1 => self.select_filter(|idx, len| (idx + 1) % len),
+            _ => {}
+        }
+    }
+
+    /// The table that has focus on the Filters tab, with its length.
+    fn focused_filters(&mut self) -> (&mut TableState, usize) {
+        match self.filter_pane {
+            FilterPane::Forums => (&mut self.filter_state, self.config.filters.forums.len()),
+            FilterPane::Titles => (&mut self.title_rule_state, self.config.filters.titles.len()),
+        }
+    }
+
+    /// Moves the focused filter table's selection to `step(current, len)`.
+    fn select_filter(&mut self, step: impl Fn(usize, usize) -> usize) {
+        let (state, len) = self.focused_filters();
+        if len == 0 {
+            state.select(None);
+            return;
+        }
+        let next = match state.selected() {
+            Some(idx) => step(idx.min(len - 1), len),
+            None => 0,
+        };
+        state.select(Some(next));
+    }
+
+    pub fn on_tab(&mut self) {
+        if self.tabs.index == 1 {
+            self.filter_pane = match self.filter_pane {
+                FilterPane::Forums => FilterPane::Titles,
+                FilterPane::Titles => FilterPane::Forums,
+            };
+        }
+    }
+
+    fn toggle_selected_filter(&mut self) {
+        let filters = &mut self.config.filters;
+        match self.filter_pane {
+            FilterPane::Forums => {
+                let selected = self.filter_state.selected();
+                if let Some(filter) = selected.and_then(|idx| filters.forums.get_mut(idx)) {
+                    filter.status = filter.status.toggled();
+                }
+            }
+            FilterPane::Titles => {
+                let selected = self.title_rule_state.selected();
+                if let Some(rule) = selected.and_then(|idx| filters.titles.get_mut(idx)) {
+                    rule.action = rule.action.toggled();
+                }
+            }
+        }
+        self.refresh_view();
+        self.save_config();
+    }
+
+    fn toggle_selected_matcher(&mut self) {
+        if self.filter_pane != FilterPane::Titles {
+            return;
+        }
+        let selected = self.title_rule_state.selected();
+        if let Some(rule) = selected.and_then(|idx| self.config.filters.titles.get_mut(idx)) {
+            let toggled = TitleRule::new(rule.pattern.clone(), rule.matcher.toggled(), rule.action);
+            if let Err(e) = toggled.validate() {
+                self.report_error(format!("Invalid regex: {}", e));
+                return;
+            }
+            *rule = toggled;
+        }
+        self.refresh_view();
+        self.save_config();
+    }
+
+    fn delete_selected_filter(&mut self) {
+        let (state, len) = self.focused_filters();
+        if let Some(idx) = state.selected() {
+            if idx < len {
+                match self.filter_pane {
+                    FilterPane::Forums => {
+                        self.config.filters.forums.remove(idx);
+                    }
+                    FilterPane::Titles => {
+                        self.config.filters.titles.remove(idx);
+                    }
+                }
+            }
+            let (state, len) = self.focused_filters();
+            if idx >= len {
+                state.select(len.checked_sub(1));
+            }
+            self.refresh_view();
+            self.save_config();
+        }
+    }
+
+    fn save_config(&mut self) {
+        if let Err(e) = self.config.save() {
+            self.report_error(format!("Failed to save config: {}", e));
+        }
+    }
+
+    /// Rebuilds the displayed topics from `threads` through the current
+    /// filters, keeping the same thread selected where possible.
+    pub fn refresh_view(&mut self) {
+        let selected = self
+            .tasks
+            .state
+            .selected()
+            .and_then(|idx| self.tasks.items.get_index(idx))
+            .map(ThwDatum::key);
+
+        let filters = &self.config.filters;
+        let show_hidden = self.show_hidden;
+        let mut hidden = 0;
+        let mut shown: Vec<&ThwDatum> = self
+            .threads
+            .iter()
+            .filter(|datum| {
+                let allowed = filters.allows(datum);
+                if !allowed {
+                    hidden += 1;
+                }
+                allowed || show_hidden
+            })
+            .collect();
+        let newest = shown.last().map(|datum| datum.key());
+        self.config.sort.sort(&mut shown);
+        self.tasks.items = shown.into_iter().cloned().collect();
+        self.hidden = hidden;
+
+        let len = self.tasks.items.len();
+        let pinned = if self.follow { newest } else { selected };
+        let reselected = pinned
+            .and_then(|key| self.tasks.items.iter().position(|datum| datum.key() == key))
+            .or_else(|| {
+                self.tasks
+                    .state
+                    .selected()
+                    .map(|idx| idx.min(len.saturating_sub(1)))
+            })
+            .filter(|_| len > 0);
+        self.tasks.state.select(reselected);
+    }
+
+    /// Handles a key press while the "add filter" box is open.
+    pub fn on_input(&mut self, code: KeyCode) {
+        let input = match self.input.as_mut() {
+            Some(input) => input,
+            None => return,
+        };
+        match code {
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Enter => {
+                let text = input.trim().to_string();
+                self.input = None;
+                if !text.is_empty() {
+                    self.add_filter(text);
+                }
+            }
+            KeyCode::Esc => self.input = None,
+            _ => {}
+        }
+    }
+
+    fn add_filter(&mut self, text: String) {
+        let filters = &mut self.config.filters;
+        match self.filter_pane {
+            FilterPane::Forums => {
+                filters.add(text);
+                self.filter_state.select(Some(filters.forums.len() - 1));
+            }
+            FilterPane::Titles => {
+                filters
+                    .titles
+                    .push(TitleRule::new(text, Matcher::Keyword, Action::Exclude));
+                self.title_rule_state.select(Some(filters.titles.len() - 1));
+            }
+        }
+        self.refresh_view();
+        self.save_config();
+    }
+
+    fn mark_selected_read(&mut self) {
+        let tasks = &self.tasks;
+        let selected = tasks
+            .state
+            .selected()
+            .and_then(|idx| tasks.items.get_index(idx));
+        if let Some(thw) = selected {
+            self.history.mark_read(&thw.key());
+        }
+    }
+
+    /// The preview of the selected thread, if there is one yet.
+    pub fn selected_preview(&self) -> Option<&Preview> {
+        let idx = self.tasks.state.selected()?;
+        let thw = self.tasks.items.get_index(idx)?;
+        self.previews.get(&thw.href)
+    }
+
+    /// Asks the preview thread for the selected thread's latest post, unless
+    /// it's been fetched already. A failed fetch is tried again once the
+    /// thread is selected anew.
+    fn request_preview(&mut self) {
+        let href = match self
+            .tasks
+            .state
+            .selected()
+            .and_then(|idx| self.tasks.items.get_index(idx))
+        {
+            Some(thw) => thw.href.clone(),
+            None => return,
+        };
+        let reselected = self.previewed.as_ref() != Some(&href);
+        self.previewed = Some(href.clone());
+        match self.previews.get(&href) {
+            Some(Preview::Failed(_)) if reselected => {}
+            Some(_) => return,
+            None => {}
+        }
+        if self.preview_sender.send(href.clone()).is_ok() {
+            self.previews.insert(href, Preview::Loading);
+        }
+    }
+
+    /// Selects the first unread thread after the current selection, wrapping
+    /// around at the end of the list.
+    fn select_next_unread(&mut self) {
+        let len = self.tasks.items.len();
+        let start = self.tasks.state.selected().map_or(0, |idx| idx + 1);
+        let history = &self.history;
+        let next = (0..len)
+            .map(|offset| (start + offset) % len)
+            .find(|&idx| !history.is_read(&self.tasks.items[idx].key()));
+        if let Some(idx) = next {
+            self.follow = false;
+            self.tasks.state.select(Some(idx));
+            self.mark_selected_read();
+        }
     }
 
     pub fn on_right(&mut self) {
@@ -196,49 +637,383 @@ impl<'a> App<'a> {
     }
 
     pub fn on_key(&mut self, c: char) {
-        match c {
-            'q' => {
-                self.should_quit = true;
-            }
-            't' => {
-                self.show_chart = !self.show_chart;
-            }
-            'c' => {
-                if let Some(uri) = self.get_uri() {
-                    let cb = Clipboard::new();
-                    match cb {
-                        Ok(mut cb) => {
-                            cb.set_text(uri).expect("failed to set clipboard");
+        let keys = &self.config.keys;
+        if self.tabs.index == 1 {
+            if c == keys.toggle_filter {
+                self.toggle_selected_filter();
+            } else if c == keys.add_filter {
+                self.input = Some(String::new());
+            } else if c == keys.delete_filter {
+                self.delete_selected_filter();
+            } else if c == keys.toggle_regex {
+                self.toggle_selected_matcher();
+            } else if c == keys.toggle_mode {
+                self.config.filters.mode = self.config.filters.mode.toggled();
+                self.refresh_view();
+                self.save_config();
+            }
+            return;
+        }
+
+        if c == keys.quit {
+            self.should_quit = true;
+        } else if c == keys.mark_all_read {
+            self.history.mark_all_read();
+        } else if c == keys.next_unread {
+            self.select_next_unread();
+        } else if c == keys.show_hidden {
+            self.show_hidden = !self.show_hidden;
+            self.refresh_view();
+        } else if c == keys.copy {
+            self.mark_selected_read();
+            if let Some(uri) = self.get_uri() {
+                let cb = Clipboard::new();
+                match cb {
+                    Ok(mut cb) => {
+                        cb.set_text(uri).expect("failed to set clipboard");
+                    }
+                    Err(e) => {
+                        self.report_error(format!("{:?} - {:?}\n", e, e.to_string()));
+                    }
+                }
+            }
+        } else if c == keys.cycle_sort {
+            self.config.sort = self.config.sort.next();
+            self.refresh_view();
+            self.save_config();
+        } else if c == keys.toggle_follow {
+            self.follow = !self.follow;
+            self.refresh_view();
+        } else if c == keys.toggle_preview {
+            self.show_preview = !self.show_preview;
+        } else if c == keys.open {
+            self.open_selected();
+        } else if c == 't' {
+            self.show_chart = !self.show_chart;
+        }
+    }
+
+    pub fn on_enter(&mut self) {
+        if self.tabs.index == 0 {
+            self.open_selected();
+        }
+    }
+
+    /// Opens the selected thread with the configured opener.
+    fn open_selected(&mut self) {
+        self.mark_selected_read();
+        let uri = match self.get_uri() {
+            Some(uri) => uri,
+            None => return,
+        };
+
+        let mut command = match opener_command(self.config.opener.as_deref()) {
+            Some(command) => command,
+            None => {
+                self.report_error("The opener command is empty".into());
+                return;
+            }
+        };
+        let spawned = command
+            .arg(uri)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        match spawned {
+            Ok(mut child) => {
+                // Reap it in the background so it doesn't linger as a zombie.
+                thread::spawn(move || child.wait());
+            }
+            Err(e) => self.report_error(format!("Failed to open thread: {}", e)),
+        }
+    }
+
+    /// Takes in one refresh's worth of threads, oldest first.
+    fn ingest(&mut self, data: Vec<ThwDatum>) -> RefreshSummary {
+        let mut summary = RefreshSummary::default();
+        // The first refresh into an empty history only fills it: everything
+        // on the feed would be new.
+        let seeding = self.history.is_empty();
+        let mut notified = 0;
+        let mut held_back = 0;
+        for datum in data {
+            let sighting = self.history.record(&datum);
+            if sighting != Sighting::Unchanged && !self.config.filters.allows(&datum) {
+                summary.hidden += 1;
+            } else if sighting == Sighting::New {
+                summary.new += 1;
+                if !seeding {
+                    let alert_now = self.config.notify.alerts_now(&datum);
+                    if alert_now {
+                        self.alert(&datum);
+                    }
+                    if alert_now && notified < notify::MAX_PER_REFRESH {
+                        notified += 1;
+                        self.notify(&datum);
+                    } else {
+                        // Past the limit, threads go to the digest instead.
+                        if alert_now {
+                            held_back += 1;
                         }
-                        Err(e) => {
-                            self.errors.push(format!("{:?} - {:?}\n", e, e.to_string()));
+                        if let Err(e) = self.queue.push(datum.clone()) {
+                            self.report_error(format!("Failed to queue for the digest: {}", e));
                         }
                     }
                 }
+            } else if sighting == Sighting::Bumped {
+                summary.updated += 1;
             }
-            _ => {}
+
+            if sighting == Sighting::Bumped {
+                // Newest activity goes to the end, like a new thread, and the
+                // old preview is stale.
+                self.previews.remove(&datum.href);
+                self.threads.shift_remove(&datum);
+                self.threads.insert(datum);
+            } else {
+                self.threads.replace(datum);
+            }
+        }
+        if held_back > 0 {
+            self.report_error(format!(
+                "{} more new threads were held back for the digest",
+                held_back
+            ));
+        }
+        summary
+    }
+
+    /// Tells every notifier about a new thread.
+    fn notify(&mut self, datum: &ThwDatum) {
+        let url = feed::url(&self.base_url, &datum.href);
+        let failures: Vec<String> = self
+            .notifiers
+            .iter_mut()
+            .filter_map(|notifier| notifier.notify(datum, &url).err())
+            .map(|e| format!("Notification failed: {}", e))
+            .collect();
+        for failure in failures {
+            self.report_error(failure);
+        }
+    }
+
+    /// Rings and flashes for a new thread, as far as the alert settings say.
+    fn alert(&mut self, datum: &ThwDatum) {
+        let alerts = &self.config.alerts;
+        if !alerts.matches(datum) {
+            return;
+        }
+        if alerts.bell {
+            self.ring_bell = true;
+        }
+        if alerts.flash {
+            self.flash_until = Some(Instant::now() + FLASH_DURATION);
+        }
+    }
+
+    /// Whether the topic list's border should be lit up right now. It blinks
+    /// on and off while the flash lasts.
+    pub fn flash_on(&self) -> bool {
+        match self.flash_until {
+            Some(until) => {
+                let left = until.saturating_duration_since(Instant::now());
+                !left.is_zero() && (left.as_millis() / 250) % 2 == 0
+            }
+            None => false,
+        }
+    }
+
+    /// The terminal title, led by the unread count, if the alert settings ask
+    /// for one.
+    pub fn window_title(&self) -> Option<String> {
+        if !self.config.alerts.title {
+            return None;
+        }
+        let filters = &self.config.filters;
+        let history = &self.history;
+        let unread = self
+            .tasks
+            .items
+            .iter()
+            .filter(|datum| filters.allows(datum) && !history.is_read(&datum.key()))
+            .count();
+        Some(match unread {
+            0 => self.title.to_string(),
+            unread => format!("({}) {}", unread, self.title),
+        })
+    }
+
+    /// Shows `error` in the footer, keeping only the most recent few.
+    pub fn report_error(&mut self, error: String) {
+        self.errors.push(error);
+        if self.errors.len() > MAX_ERRORS {
+            self.errors.remove(0);
         }
     }
 
     pub fn on_tick(&mut self) {
-        self.progress =
-            (Instant::now() - self.next_update.checked_sub(Duration::from_secs(60)).unwrap()).as_secs() as f64 / 60.0;
+        self.progress = (Instant::now() - (self.next_update - self.refresh_interval)).as_secs_f64()
+            / self.refresh_interval.as_secs_f64();
         if self.progress >= 1.0 {
-            self.next_update = Instant::now() + Duration::from_secs(60);
+            self.next_update = Instant::now() + self.refresh_interval;
             self.progress = 0.0;
             self.refresh_sender
                 .send(())
                 .expect("Failed to send a refresh");
         }
 
-        let new_res = self.results_receiver.try_recv();
-        if let Ok(res) = new_res {
-            if !self.filters.contains(&res.forum) {
-                self.tasks.items.insert(res);
+        let results: Vec<_> = self.results_receiver.try_iter().collect();
+        let mut summary = None;
+        for result in results {
+            match result {
+                Ok(data) => {
+                    self.errors.clear();
+                    summary = Some(summary.unwrap_or_default() + self.ingest(data));
+                }
+                Err(e) => self.report_error(format!("Refresh failed: {}", e)),
             }
         }
+        if summary.is_some() {
+            self.last_refresh = summary;
+            self.refresh_view();
+        }
+
+        for (href, post) in self.preview_receiver.try_iter() {
+            let preview = match post {
+                Ok(post) => Preview::Ready(post),
+                Err(e) => Preview::Failed(e.to_string()),
+            };
+            self.previews.insert(href, preview);
+        }
+        if self.show_preview {
+            self.request_preview();
+        }
+
+        if let Err(e) = self.history.save() {
+            self.report_error(format!("Failed to save history: {}", e));
+        }
 
         self.sparkline.on_tick();
         self.signals.on_tick();
     }
 }
+
+/// Builds the command that opens a URL, given as its last argument.
+fn opener_command(opener: Option<&str>) -> Option<Command> {
+    let default = if cfg!(target_os = "windows") {
+        "explorer"
+    } else if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    let mut words = opener.unwrap_or(default).split_whitespace();
+    let mut command = Command::new(words.next()?);
+    command.args(words);
+    Some(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::error::Error;
+    use std::rc::Rc;
+
+    /// Remembers the ids of the threads it's told about.
+    struct Recorder(Rc<RefCell<Vec<u64>>>);
+
+    impl Notifier for Recorder {
+        fn notify(&mut self, datum: &ThwDatum, _url: &str) -> Result<(), Box<dyn Error>> {
+            self.0.borrow_mut().extend(datum.thread_id);
+            Ok(())
+        }
+    }
+
+    /// An app with no feed or preview thread behind it, whose refreshes are
+    /// handed straight to `ingest`.
+    fn app(config: Config) -> App<'static> {
+        let (refresh_sender, _) = mpsc::channel();
+        let (_, results_receiver) = mpsc::channel();
+        let (preview_sender, _) = mpsc::channel();
+        let (_, preview_receiver) = mpsc::channel();
+        App::new(
+            "test",
+            false,
+            "https://x.org/".into(),
+            config,
+            refresh_sender,
+            results_receiver,
+            History::in_memory(),
+            Queue::in_memory(),
+            preview_sender,
+            preview_receiver,
+        )
+    }
+
+    fn thread(id: u64, replies: u64) -> ThwDatum {
+        let mut datum = ThwDatum::bare(
+            "A thread",
+            "Triggers & Scripts",
+            &format!("threads/a.{}/", id),
+        );
+        datum.replies = Some(replies);
+        datum
+    }
+
+    fn ids(threads: &IndexSet<ThwDatum>) -> Vec<u64> {
+        threads.iter().filter_map(|datum| datum.thread_id).collect()
+    }
+
+    #[test]
+    fn bumped_threads_move_to_the_end() {
+        let mut app = app(Config::default());
+        app.ingest(vec![thread(1, 0), thread(2, 0), thread(3, 0)]);
+        app.previews
+            .insert("threads/a.1/".into(), Preview::Failed("old".into()));
+
+        app.ingest(vec![thread(1, 1), thread(2, 0)]);
+        assert_eq!(ids(&app.threads), [2, 3, 1]);
+        assert!(app.previews.is_empty());
+        assert!(app.history.is_bumped("1"));
+        assert!(!app.history.is_bumped("2"));
+    }
+
+    #[test]
+    fn summarises_each_refresh() {
+        let mut config = Config::default();
+        config.filters.add("Off-Topic".into());
+        let mut app = app(config);
+        let mut off_topic = thread(3, 0);
+        off_topic.forum = "Off-Topic".into();
+
+        let summary = app.ingest(vec![thread(1, 0), thread(2, 0), off_topic.clone()]);
+        assert_eq!((summary.new, summary.updated, summary.hidden), (2, 0, 1));
+        assert_eq!(summary.to_string(), "+2 new, 0 updated, 1 hidden");
+
+        // Hidden threads are counted once, unless they're bumped.
+        let summary = app.ingest(vec![thread(1, 1), thread(2, 0), off_topic, thread(4, 0)]);
+        assert_eq!((summary.new, summary.updated, summary.hidden), (1, 1, 0));
+    }
+
+    #[test]
+    fn notifies_after_the_first_refresh_up_to_the_limit() {
+        let mut app = app(Config::default());
+        let notified = Rc::new(RefCell::new(vec![]));
+        app.notifiers.push(Box::new(Recorder(notified.clone())));
+
+        // Everything is new to an empty history.
+        app.ingest(vec![thread(1, 0), thread(2, 0)]);
+        assert!(notified.borrow().is_empty());
+        assert!(app.queue.threads().is_empty());
+
+        // Two more than the limit: those are held back for the digest.
+        let limit = notify::MAX_PER_REFRESH as u64;
+        app.ingest((3..5 + limit).map(|id| thread(id, 0)).collect());
+        assert_eq!(*notified.borrow(), (3..3 + limit).collect::<Vec<_>>());
+        let queued: Vec<_> = app.queue.threads().iter().map(|d| d.thread_id).collect();
+        assert_eq!(queued, [Some(3 + limit), Some(4 + limit)]);
+    }
+}
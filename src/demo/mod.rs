@@ -1,4 +1,6 @@
 mod app;
 pub mod ui;
 pub use app::App;
+pub use app::FilterPane;
+pub use app::Preview;
 pub use app::ThwDatum;
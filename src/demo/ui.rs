@@ -2,14 +2,16 @@ use tui::{
     backend::Backend,
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
-    text::{Span, Spans},
+    text::{Span, Spans, Text},
     widgets::{Block, Borders, Cell, Gauge, List, ListItem, Paragraph, Row, Table, Tabs, Wrap},
     Frame,
 };
 
 use std::time::Instant;
 
-use crate::demo::App;
+use crate::demo::{App, FilterPane, Preview, ThwDatum};
+use crate::filter::{Action, Status};
+use crate::history;
 
 pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
     let chunks = Layout::default()
@@ -22,15 +24,18 @@ pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         .split(chunks[0]);
     let tabs = Tabs::new(app.tabs.titles.iter().cloned().map(Spans::from).collect())
         .block(Block::default().borders(Borders::ALL).title(app.title))
-        .style(Style::default().fg(Color::Green))
-        .highlight_style(Style::default().fg(Color::Yellow))
+        .style(Style::default().fg(app.config.colors.tabs))
+        .highlight_style(Style::default().fg(app.config.colors.selected_tab))
         .select(app.tabs.index);
     f.render_widget(tabs, tab_chunks[0]);
 
-    let label = format!(
+    let mut label = format!(
         "Reload in {:.0}s",
         (app.next_update - Instant::now()).as_secs() + 1
     );
+    if let Some(summary) = app.last_refresh {
+        label = format!("{} · last refresh {}", label, summary);
+    }
     let gauge = Gauge::default()
         .block(Block::default().borders(Borders::ALL))
         .style(
@@ -65,29 +70,132 @@ fn draw_charts<B>(f: &mut Frame<B>, app: &mut App, area: Rect)
 where
     B: Backend,
 {
+    let constraints = if app.show_preview {
+        vec![Constraint::Percentage(50), Constraint::Percentage(50)]
+    } else {
+        vec![Constraint::Percentage(100)]
+    };
     let chunks = Layout::default()
-        .constraints(vec![Constraint::Percentage(100)])
+        .constraints(constraints)
         .direction(Direction::Horizontal)
         .split(area);
     {
         // Draw tasks
+        let history = &app.history;
+        let filters = &app.config.filters;
+        let now = history::now();
         let tasks = app
             .tasks
             .items
             .iter()
-            .map(|dat| format!("{} :: {}", dat.forum, dat.title))
-            .map(ListItem::new)
+            .map(|dat| {
+                let key = dat.key();
+                let marker = if history.is_bumped(&key) {
+                    Span::styled("updated ", Style::default().fg(app.config.colors.updated))
+                } else {
+                    Span::raw("")
+                };
+                let item = ListItem::new(Spans::from(vec![
+                    marker,
+                    Span::raw(format!("{} :: {}", dat.forum, dat.title)),
+                    Span::styled(details(dat, now), Style::default().fg(Color::DarkGray)),
+                ]));
+                if !filters.allows(dat) {
+                    item.style(Style::default().add_modifier(Modifier::DIM))
+                } else if history.is_read(&key) {
+                    item
+                } else {
+                    item.style(Style::default().add_modifier(Modifier::BOLD))
+                }
+            })
             .collect::<Vec<_>>();
+        let mut notes = vec![app.config.sort.label().to_string()];
+        match (app.hidden, app.show_hidden) {
+            (0, _) => {}
+            (hidden, false) => notes.push(format!("{} hidden", hidden)),
+            (hidden, true) => notes.push(format!("showing {} hidden", hidden)),
+        }
+        if app.follow {
+            notes.push("following".to_string());
+        }
+        let title = format!("Topics ({})", notes.join(", "));
+        let mut block = Block::default().borders(Borders::ALL).title(title);
+        if app.flash_on() {
+            block = block.border_style(Style::default().fg(app.config.colors.flash));
+        }
         let tasks = List::new(tasks)
-            .block(Block::default().borders(Borders::ALL).title("Topics"))
+            .block(block)
             .highlight_style(
                 Style::default()
-                    .fg(Color::Yellow)
+                    .fg(app.config.colors.selection)
                     .add_modifier(Modifier::BOLD),
             )
             .highlight_symbol("> ");
         f.render_stateful_widget(tasks, chunks[0], &mut app.tasks.state);
     }
+    if app.show_preview {
+        draw_preview(f, app, chunks[1]);
+    }
+}
+
+/// Who started the thread, and how lively it is, for the topic list.
+fn details(dat: &ThwDatum, now: u64) -> String {
+    let mut parts = vec![];
+    if let Some(starter) = &dat.starter {
+        parts.push(format!("by {}", starter));
+    }
+    if let Some(replies) = dat.replies {
+        parts.push(format!("{} replies", replies));
+    }
+    if let Some(views) = dat.views {
+        parts.push(format!("{} views", views));
+    }
+    match (&dat.last_poster, dat.last_post_at) {
+        (Some(poster), Some(at)) => parts.push(format!("last {} {}", poster, ago(now, at))),
+        (Some(poster), None) => parts.push(format!("last {}", poster)),
+        (None, Some(at)) => parts.push(format!("last post {}", ago(now, at))),
+        (None, None) => {}
+    }
+    if parts.is_empty() {
+        String::new()
+    } else {
+        format!("  ({})", parts.join(", "))
+    }
+}
+
+/// How long before `now` the unix time `then` was, roughly.
+fn ago(now: u64, then: u64) -> String {
+    match now.saturating_sub(then) {
+        secs if secs < 60 => "just now".to_string(),
+        secs if secs < 60 * 60 => format!("{}m ago", secs / 60),
+        secs if secs < 24 * 60 * 60 => format!("{}h ago", secs / (60 * 60)),
+        secs => format!("{}d ago", secs / (24 * 60 * 60)),
+    }
+}
+
+fn draw_preview<B>(f: &mut Frame<B>, app: &App, area: Rect)
+where
+    B: Backend,
+{
+    let (title, text) = match app.selected_preview() {
+        Some(Preview::Ready(post)) => (
+            match &post.author {
+                Some(author) => format!("Latest post by {}", author),
+                None => "Latest post".to_string(),
+            },
+            Text::from(post.text.as_str()),
+        ),
+        Some(Preview::Failed(e)) => (
+            "Latest post".to_string(),
+            Text::styled(e.as_str(), Style::default().fg(app.config.colors.error)),
+        ),
+        Some(Preview::Loading) => ("Latest post".to_string(), Text::raw("Loading…")),
+        None => ("Latest post".to_string(), Text::raw("")),
+    };
+    let paragraph = Paragraph::new(text)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .wrap(Wrap { trim: false });
+    f.render_widget(paragraph, area);
 }
 
 // Footer
@@ -104,7 +212,7 @@ where
         _ => app
             .errors
             .iter()
-            .map(|str| Span::styled(str, Style::default().bg(Color::Magenta)))
+            .map(|str| Span::styled(str, Style::default().bg(app.config.colors.error)))
             .map(Spans::from)
             .collect(),
     };
@@ -115,20 +223,38 @@ where
     let paragraph = Paragraph::new(text).block(block).wrap(Wrap { trim: true });
 
     let chunks = Layout::default()
-        .constraints([Constraint::Min(40), Constraint::Length(10)].as_ref())
+        .constraints([Constraint::Min(40), Constraint::Length(34)].as_ref())
         .direction(Direction::Horizontal)
         .split(area);
     f.render_widget(paragraph, chunks[0]);
 
-    let legend_text: Vec<_> = vec![
-        Span::styled("Q", Style::default().add_modifier(Modifier::UNDERLINED)),
-        Span::raw("uit\n"),
-        Span::styled("C", Style::default().add_modifier(Modifier::UNDERLINED)),
-        Span::raw("opy url\n"),
-    ]
-    .into_iter()
-    .map(Spans::from)
-    .collect();
+    let keys = &app.config.keys;
+    let legend = [
+        (keys.quit, "quit"),
+        (keys.open, "open"),
+        (keys.copy, "copy url"),
+        (keys.next_unread, "next unread"),
+        (keys.mark_all_read, "all read"),
+        (keys.show_hidden, "show hidden"),
+        (keys.toggle_preview, "preview"),
+        (keys.cycle_sort, "sort"),
+        (keys.toggle_follow, "follow newest"),
+    ];
+    // Two columns, so the footer stays short.
+    let legend_text = legend
+        .chunks(2)
+        .map(|row| {
+            let mut spans = vec![];
+            for (c, label) in row {
+                spans.push(Span::styled(
+                    c.to_string(),
+                    Style::default().fg(Color::Yellow),
+                ));
+                spans.push(Span::raw(format!(" {:14}", label)));
+            }
+            Spans::from(spans)
+        })
+        .collect::<Vec<_>>();
     let legend = Paragraph::new(legend_text).block(Block::default().borders(Borders::ALL));
     f.render_widget(legend, chunks[1]);
 }
@@ -137,20 +263,100 @@ fn draw_second_tab<B>(f: &mut Frame<B>, app: &mut App, area: Rect)
 where
     B: Backend,
 {
-    let blocked_style = Style::default().fg(Color::Red);
-    let header = ["Forum", "Status"];
-    let rows = app.filters.iter().map(|s| {
-        Row::new(
-            vec![
-                Cell::from(s.to_string()).style(blocked_style),
-                Cell::from("Blocked".to_string()).style(blocked_style),
+    let chunks = Layout::default()
+        .constraints(
+            [
+                Constraint::Min(0),
+                Constraint::Length(if app.input.is_some() { 3 } else { 0 }),
             ]
-            .into_iter(),
+            .as_ref(),
         )
+        .split(area);
+
+    let panes = Layout::default()
+        .constraints(vec![Constraint::Percentage(45), Constraint::Percentage(55)])
+        .direction(Direction::Horizontal)
+        .split(chunks[0]);
+    let colors = &app.config.colors;
+    let keys = &app.config.keys;
+    let hints = format!(
+        "{:?}: toggle, {:?}: add, {:?}: delete, Tab: switch",
+        keys.toggle_filter, keys.add_filter, keys.delete_filter
+    );
+    let focus = app.filter_pane;
+    let pane_block = |title: String, pane: FilterPane| {
+        let block = Block::default().borders(Borders::ALL);
+        if focus == pane {
+            block
+                .title(format!("{} - {}", title, hints))
+                .border_style(Style::default().fg(colors.selection))
+        } else {
+            block.title(title)
+        }
+    };
+    let header_style = Style::default().fg(colors.selection);
+
+    let rows = app.config.filters.forums.iter().map(|filter| {
+        let style = match filter.status {
+            Status::Blocked => Style::default().fg(colors.blocked),
+            Status::Allowed => Style::default().fg(colors.allowed),
+        };
+        Row::new(vec![
+            Cell::from(filter.forum.clone()),
+            Cell::from(filter.status.label()),
+        ])
+        .style(style)
     });
     let table = Table::new(rows)
-        .header(Row::new(header).style(Style::default().fg(Color::Yellow)))
-        .block(Block::default().borders(Borders::ALL))
-        .widths(&[Constraint::Length(30), Constraint::Length(20)]);
-    f.render_widget(table, area);
+        .header(Row::new(vec!["Forum", "Status"]).style(header_style))
+        .block(pane_block(
+            format!(
+                "Forums ({}, {:?}: switch)",
+                app.config.filters.mode.label(),
+                keys.toggle_mode
+            ),
+            FilterPane::Forums,
+        ))
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ")
+        .widths(&[Constraint::Length(30), Constraint::Length(10)]);
+    f.render_stateful_widget(table, panes[0], &mut app.filter_state);
+
+    let rows = app.config.filters.titles.iter().map(|rule| {
+        let style = match rule.action {
+            Action::Exclude => Style::default().fg(colors.blocked),
+            Action::Include => Style::default().fg(colors.allowed),
+        };
+        Row::new(vec![
+            Cell::from(rule.pattern.clone()),
+            Cell::from(rule.matcher.label()),
+            Cell::from(rule.action.label()),
+        ])
+        .style(style)
+    });
+    let table = Table::new(rows)
+        .header(Row::new(vec!["Title", "Match", "Action"]).style(header_style))
+        .block(pane_block(
+            format!("Title rules ({:?}: regex)", keys.toggle_regex),
+            FilterPane::Titles,
+        ))
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ")
+        .widths(&[
+            Constraint::Min(20),
+            Constraint::Length(8),
+            Constraint::Length(8),
+        ]);
+    f.render_stateful_widget(table, panes[1], &mut app.title_rule_state);
+
+    if let Some(input) = &app.input {
+        let text = Spans::from(vec![Span::raw(input.as_str()), Span::raw("_")]);
+        let input_box = Paragraph::new(text).block(Block::default().borders(Borders::ALL).title(
+            match app.filter_pane {
+                FilterPane::Forums => "Forum to add (Enter to confirm, Esc to cancel)",
+                FilterPane::Titles => "Title keyword to exclude (Enter to confirm, Esc to cancel)",
+            },
+        ));
+        f.render_widget(input_box, chunks[1]);
+    }
 }
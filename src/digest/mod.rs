@@ -0,0 +1,522 @@
+mod smtp;
+
+use crate::cli;
+use crate::config::Config;
+use crate::demo::ThwDatum;
+use crate::feed::{self, HiveSource};
+use crate::headless;
+use crate::history::{History, Sighting};
+use crate::lock;
+
+use chrono::{DateTime, Days, Local, TimeZone, Timelike};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// Longest window that can be given in seconds: a year.
+const MAX_WINDOW_SECS: u64 = 366 * 24 * 60 * 60;
+
+/// Most threads kept waiting for a digest, dropping the oldest first, in case
+/// `digest` never runs.
+const MAX_QUEUED: usize = 1000;
+
+/// How long a digest collects threads for.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Window {
+    /// Until the top of the hour.
+    Hourly,
+    /// Until midnight, local time.
+    #[default]
+    Daily,
+    /// This many seconds, up to `MAX_WINDOW_SECS`.
+    Every(u64),
+}
+
+impl Window {
+    /// When the window that's open at `now` closes.
+    pub fn end_after(self, now: DateTime<Local>) -> DateTime<Local> {
+        let hour = now
+            .with_minute(0)
+            .and_then(|now| now.with_second(0))
+            .and_then(|now| now.with_nanosecond(0));
+        match self {
+            Window::Hourly => hour.unwrap_or(now) + chrono::Duration::hours(1),
+            Window::Daily => now
+                .date_naive()
+                .checked_add_days(Days::new(1))
+                .and_then(|day| day.and_hms_opt(0, 0, 0))
+                .and_then(|midnight| Local.from_local_datetime(&midnight).earliest())
+                .unwrap_or_else(|| now + chrono::Duration::days(1)),
+            Window::Every(secs) => now + chrono::Duration::seconds(secs as i64),
+        }
+    }
+}
+
+impl FromStr for Window {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Window, String> {
+        match s {
+            "hourly" => Ok(Window::Hourly),
+            "daily" => Ok(Window::Daily),
+            _ => match s.parse() {
+                Ok(secs) if secs > 0 && secs <= MAX_WINDOW_SECS => Ok(Window::Every(secs)),
+                _ => Err(format!(
+                    "unknown window {:?}, expected hourly, daily or a number of seconds up to {}",
+                    s, MAX_WINDOW_SECS
+                )),
+            },
+        }
+    }
+}
+
+impl TryFrom<String> for Window {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Window, String> {
+        s.parse()
+    }
+}
+
+impl From<Window> for String {
+    fn from(window: Window) -> String {
+        match window {
+            Window::Hourly => "hourly".into(),
+            Window::Daily => "daily".into(),
+            Window::Every(secs) => secs.to_string(),
+        }
+    }
+}
+
+/// What a digest is written as.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Text,
+    Markdown,
+    Html,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Format, String> {
+        match s {
+            "text" => Ok(Format::Text),
+            "markdown" => Ok(Format::Markdown),
+            "html" => Ok(Format::Html),
+            _ => Err(format!(
+                "unknown format {:?}, expected text, markdown or html",
+                s
+            )),
+        }
+    }
+}
+
+/// Where digests are sent by SMTP. There's no TLS or authentication, so this
+/// is meant for a local relay.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Smtp {
+    /// `host:port` of the server.
+    pub server: String,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+/// Threads waiting for the next digest, kept on disk so that they survive a
+/// restart. Other processes may queue threads or send digests too, so every
+/// change re-reads the file and writes it back under a lock.
+pub struct Queue {
+    path: Option<PathBuf>,
+    queued: Queued,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Queued {
+    /// Unix time of the first thread in the queue.
+    since: Option<i64>,
+    threads: Vec<ThwDatum>,
+}
+
+impl Queue {
+    /// Reads the queue at `path`, which doesn't have to exist yet.
+    pub fn load(path: PathBuf) -> io::Result<Queue> {
+        Ok(Queue {
+            queued: read(&path)?,
+            path: Some(path),
+        })
+    }
+
+    /// A queue that is never written to disk.
+    pub fn in_memory() -> Queue {
+        Queue {
+            path: None,
+            queued: Queued::default(),
+        }
+    }
+
+    /// Adds `datum`, unless it's queued already, making room by dropping the
+    /// oldest thread past `MAX_QUEUED`.
+    pub fn push(&mut self, datum: ThwDatum) -> io::Result<()> {
+        self.update(|queued| {
+            if queued.threads.contains(&datum) {
+                return;
+            }
+            if queued.threads.is_empty() {
+                queued.since = Some(Local::now().timestamp());
+            }
+            queued.threads.push(datum);
+            let excess = queued.threads.len().saturating_sub(MAX_QUEUED);
+            queued.threads.drain(..excess);
+        })
+    }
+
+    /// Catches up with what other processes queued or sent.
+    pub fn reload(&mut self) -> io::Result<()> {
+        self.update(|_| {})
+    }
+
+    /// The threads as of the last change or reload.
+    pub fn threads(&self) -> &[ThwDatum] {
+        &self.queued.threads
+    }
+
+    /// When the first thread in the queue was added.
+    pub fn since(&self) -> Option<DateTime<Local>> {
+        self.queued
+            .since
+            .and_then(|since| Local.timestamp_opt(since, 0).single())
+    }
+
+    /// Takes the threads that were sent off the queue, leaving any that were
+    /// queued since.
+    pub fn remove(&mut self, sent: &[ThwDatum]) -> io::Result<()> {
+        self.update(|queued| {
+            queued.threads.retain(|datum| !sent.contains(datum));
+            queued.since = if queued.threads.is_empty() {
+                None
+            } else {
+                Some(Local::now().timestamp())
+            };
+        })
+    }
+
+    /// Applies `change` to the latest queue on disk and writes it back.
+    fn update<F>(&mut self, change: F) -> io::Result<()>
+    where
+        F: FnOnce(&mut Queued),
+    {
+        let path = match &self.path {
+            Some(path) => path,
+            None => {
+                change(&mut self.queued);
+                return Ok(());
+            }
+        };
+        let _lock = lock::lock(path)?;
+        self.queued = read(path)?;
+        change(&mut self.queued);
+        let json = serde_json::to_string_pretty(&self.queued)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        lock::write(path, &json)
+    }
+}
+
+fn read(path: &Path) -> io::Result<Queued> {
+    match fs::read_to_string(path) {
+        Ok(json) => {
+            serde_json::from_str(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Queued::default()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Keeps refreshing, collects the new threads that pass the filters, and sends
+/// them as one digest each time the window closes. Returns the status to exit
+/// with, which is only ever nonzero with `--now`.
+pub fn run(
+    args: cli::Digest,
+    config: Config,
+    base_url: String,
+    pages: usize,
+    mut history: History,
+    mut queue: Queue,
+) -> Result<i32, Box<dyn Error>> {
+    let settings = &config.digest;
+    let window = args.window.unwrap_or(settings.window);
+    let format = args.format.unwrap_or(settings.format);
+    let output = args.output.or_else(|| settings.output.clone());
+    let send = |queue: &mut Queue, until| -> Result<(), Box<dyn Error>> {
+        // Pick up the threads that the terminal UI or `watch` held back.
+        queue.reload()?;
+        let threads = queue.threads().to_vec();
+        if threads.is_empty() {
+            return Ok(());
+        }
+        let since = queue.since().unwrap_or(until);
+        let digest = render(format, &threads, &base_url, since, until);
+        if let Some(smtp) = &settings.smtp {
+            smtp::send(smtp, &subject(threads.len()), format, &digest)?;
+        }
+        match &output {
+            Some(path) => write_file(path, &digest, until)?,
+            None if settings.smtp.is_none() => println!("{}", digest),
+            None => {}
+        }
+        queue.remove(&threads)?;
+        Ok(())
+    };
+
+    let (refresh_tx, refresh_rx) = mpsc::channel();
+    let (results_tx, results_rx) = mpsc::channel();
+    let _handle = feed::spawn(
+        HiveSource::new(base_url.clone(), pages),
+        refresh_rx,
+        results_tx,
+    );
+
+    let refresh_interval = Duration::from_secs(config.refresh_interval);
+    let mut next_update = Instant::now();
+    let mut until = window.end_after(Local::now());
+
+    loop {
+        let now = Instant::now();
+        if now >= next_update {
+            refresh_tx.send(())?;
+            next_update = now + refresh_interval;
+        }
+
+        let to_digest = (until - Local::now()).to_std().unwrap_or_default();
+        match results_rx.recv_timeout(to_digest.min(next_update - now)) {
+            Ok(Ok(data)) => {
+                for datum in data {
+                    if history.record(&datum) == Sighting::New && config.filters.allows(&datum) {
+                        queue.push(datum)?;
+                    }
+                }
+                history.save()?;
+                // With --now, the first refresh is all we wait for.
+                if args.now {
+                    send(&mut queue, Local::now())?;
+                    return Ok(0);
+                }
+            }
+            // With --now, don't wait out the retries: send what was queued
+            // before, and fail.
+            Ok(Err(e)) if args.now => {
+                eprintln!("Refresh failed: {}", e);
+                send(&mut queue, Local::now())?;
+                return Ok(headless::EXIT_FETCH_FAILED);
+            }
+            Ok(Err(e)) => eprintln!("Refresh failed: {}", e),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return Ok(0),
+        }
+
+        if Local::now() >= until {
+            // Keep the queue for next time if the digest couldn't be sent.
+            if let Err(e) = send(&mut queue, until) {
+                eprintln!("Sending the digest failed: {}", e);
+            }
+            until = window.end_after(Local::now());
+        }
+    }
+}
+
+fn subject(count: usize) -> String {
+    match count {
+        1 => "Hive Workshop digest: 1 new thread".into(),
+        count => format!("Hive Workshop digest: {} new threads", count),
+    }
+}
+
+/// The threads grouped by forum, in the order each forum first came up.
+fn by_forum(threads: &[ThwDatum]) -> IndexMap<&str, Vec<&ThwDatum>> {
+    let mut forums: IndexMap<&str, Vec<&ThwDatum>> = IndexMap::new();
+    for datum in threads {
+        forums.entry(&datum.forum).or_default().push(datum);
+    }
+    forums
+}
+
+/// Who started the thread and how many replies it has, where known.
+fn details(datum: &ThwDatum) -> String {
+    match (&datum.starter, datum.replies) {
+        (Some(starter), Some(replies)) => format!(" by {}, {} replies", starter, replies),
+        (Some(starter), None) => format!(" by {}", starter),
+        (None, Some(replies)) => format!(" ({} replies)", replies),
+        (None, None) => String::new(),
+    }
+}
+
+fn render(
+    format: Format,
+    threads: &[ThwDatum],
+    base_url: &str,
+    since: DateTime<Local>,
+    until: DateTime<Local>,
+) -> String {
+    let period = format!(
+        "{} to {}",
+        since.format("%Y-%m-%d %H:%M"),
+        until.format("%Y-%m-%d %H:%M")
+    );
+    let mut out = String::new();
+    // Writing to a String can't fail.
+    match format {
+        Format::Text => {
+            let _ = writeln!(out, "{}, {}", subject(threads.len()), period);
+            for (forum, threads) in by_forum(threads) {
+                let _ = writeln!(out, "\n{}", forum);
+                for datum in threads {
+                    let _ = writeln!(
+                        out,
+                        "  {}{} :: {}",
+                        datum.title,
+                        details(datum),
+                        feed::url(base_url, &datum.href)
+                    );
+                }
+            }
+        }
+        Format::Markdown => {
+            let _ = writeln!(out, "# {}\n\n_{}_", subject(threads.len()), period);
+            for (forum, threads) in by_forum(threads) {
+                let _ = writeln!(out, "\n## {}\n", forum);
+                for datum in threads {
+                    let _ = writeln!(
+                        out,
+                        "- [{}]({}){}",
+                        datum.title.replace('[', "\\[").replace(']', "\\]"),
+                        feed::url(base_url, &datum.href),
+                        details(datum)
+                    );
+                }
+            }
+        }
+        Format::Html => {
+            let title = html_escape(&subject(threads.len()));
+            let _ = writeln!(
+                out,
+                "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{}</title></head>\n<body>\n<h1>{}</h1>\n<p><em>{}</em></p>",
+                title, title, period
+            );
+            for (forum, threads) in by_forum(threads) {
+                let _ = writeln!(out, "<h2>{}</h2>\n<ul>", html_escape(forum));
+                for datum in threads {
+                    let _ = writeln!(
+                        out,
+                        "<li><a href=\"{}\">{}</a>{}</li>",
+                        html_escape(&feed::url(base_url, &datum.href)),
+                        html_escape(&datum.title),
+                        html_escape(&details(datum))
+                    );
+                }
+                let _ = writeln!(out, "</ul>");
+            }
+            let _ = writeln!(out, "</body>\n</html>");
+        }
+    }
+    out.trim_end().to_string()
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Writes the digest to `path`, where `{time}` stands for the end of its
+/// window so that each digest can get a file of its own.
+fn write_file(path: &Path, digest: &str, until: DateTime<Local>) -> io::Result<()> {
+    let path = PathBuf::from(
+        path.to_string_lossy()
+            .replace("{time}", &until.format("%Y-%m-%dT%H-%M").to_string()),
+    );
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, format!("{}\n", digest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(day: u32, hour: u32, minute: u32, second: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2024, 5, day, hour, minute, second)
+            .unwrap()
+    }
+
+    #[test]
+    fn sending_leaves_threads_queued_elsewhere() {
+        let dir = std::env::temp_dir().join(format!("thw-digest-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("digest.json");
+        let mut tui = Queue::load(path.clone()).unwrap();
+        let mut digest = Queue::load(path.clone()).unwrap();
+
+        tui.push(ThwDatum::bare("A", "Maps", "threads/a.1/"))
+            .unwrap();
+        digest.reload().unwrap();
+        let sent = digest.threads().to_vec();
+        assert_eq!(sent.len(), 1);
+        tui.push(ThwDatum::bare("B", "Maps", "threads/b.2/"))
+            .unwrap();
+        digest.remove(&sent).unwrap();
+        // Already queued, and not sent again.
+        tui.push(ThwDatum::bare("B", "Maps", "threads/b.2/"))
+            .unwrap();
+
+        let queued = Queue::load(path).unwrap();
+        let titles: Vec<&str> = queued
+            .threads()
+            .iter()
+            .map(|datum| &datum.title[..])
+            .collect();
+        assert_eq!(titles, ["B"]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn drops_the_oldest_threads_past_the_cap() {
+        let mut queue = Queue::in_memory();
+        for id in 0..MAX_QUEUED + 2 {
+            let datum = ThwDatum::bare("A", "Maps", &format!("threads/a.{}/", id));
+            queue.push(datum).unwrap();
+        }
+        assert_eq!(queue.threads().len(), MAX_QUEUED);
+        assert_eq!(queue.threads()[0].thread_id, Some(2));
+    }
+
+    #[test]
+    fn windows_end() {
+        let now = local(10, 13, 25, 40);
+        assert_eq!(Window::Hourly.end_after(now), local(10, 14, 0, 0));
+        assert_eq!(Window::Daily.end_after(now), local(11, 0, 0, 0));
+        assert_eq!(Window::Every(90).end_after(now), local(10, 13, 27, 10));
+    }
+
+    #[test]
+    fn parses_windows() {
+        assert_eq!("hourly".parse(), Ok(Window::Hourly));
+        assert_eq!("3600".parse(), Ok(Window::Every(3600)));
+        assert!("0".parse::<Window>().is_err());
+        assert!("10000000000000000".parse::<Window>().is_err());
+        assert!("weekly".parse::<Window>().is_err());
+    }
+}
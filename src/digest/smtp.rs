@@ -0,0 +1,199 @@
+use crate::digest::{Format, Smtp};
+
+use chrono::Local;
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Sends `body` as a mail to everyone in `smtp.to`, speaking just enough SMTP
+/// for a local relay: no TLS, no authentication.
+pub fn send(smtp: &Smtp, subject: &str, format: Format, body: &str) -> io::Result<()> {
+    let mut stream = TcpStream::connect(&smtp.server)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    expect(&mut reader, &[220])?;
+    command(&mut stream, &mut reader, "EHLO thw-subscriber", &[250])?;
+    command(
+        &mut stream,
+        &mut reader,
+        &format!("MAIL FROM:<{}>", smtp.from),
+        &[250],
+    )?;
+    for to in &smtp.to {
+        command(
+            &mut stream,
+            &mut reader,
+            &format!("RCPT TO:<{}>", to),
+            &[250, 251],
+        )?;
+    }
+    command(&mut stream, &mut reader, "DATA", &[354])?;
+
+    let content_type = match format {
+        Format::Html => "text/html",
+        Format::Text | Format::Markdown => "text/plain",
+    };
+    let mut message = format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMIME-Version: 1.0\r\nContent-Type: {}; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n",
+        smtp.from,
+        smtp.to.join(", "),
+        subject,
+        Local::now().to_rfc2822(),
+        content_type
+    );
+    for line in body.lines() {
+        // A lone "." would end the message early, so lines starting with one
+        // get another.
+        if line.starts_with('.') {
+            message.push('.');
+        }
+        message.push_str(line);
+        message.push_str("\r\n");
+    }
+    message.push_str(".\r\n");
+    stream.write_all(message.as_bytes())?;
+    expect(&mut reader, &[250])?;
+
+    command(&mut stream, &mut reader, "QUIT", &[221])
+}
+
+fn command(
+    stream: &mut TcpStream,
+    reader: &mut impl BufRead,
+    line: &str,
+    codes: &[u16],
+) -> io::Result<()> {
+    stream.write_all(format!("{}\r\n", line).as_bytes())?;
+    expect(reader, codes)
+}
+
+/// Reads a reply, which may span several lines, and fails unless its code is
+/// one of `codes`.
+fn expect(reader: &mut impl BufRead, codes: &[u16]) -> io::Result<()> {
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the SMTP server hung up",
+            ));
+        }
+        let code = line.get(..3).and_then(|code| code.parse().ok());
+        // "250-..." has more lines to come, "250 ..." is the last.
+        if line.as_bytes().get(3) == Some(&b'-') {
+            continue;
+        }
+        return match code {
+            Some(code) if codes.contains(&code) => Ok(()),
+            _ => Err(io::Error::other(format!(
+                "the SMTP server said {}",
+                line.trim_end()
+            ))),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Plays a mail server that answers with `replies` in turn, one per line
+    /// it reads (the whole message counts as one line after DATA), and returns
+    /// everything the client sent.
+    fn serve(replies: Vec<&'static str>) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let server = listener.local_addr().unwrap().to_string();
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut replies = replies.into_iter();
+            stream
+                .write_all(replies.next().unwrap().as_bytes())
+                .unwrap();
+            let mut sent = vec![];
+            let mut in_data = false;
+            for reply in replies {
+                let mut line = String::new();
+                if in_data {
+                    while !line.ends_with("\r\n.\r\n") {
+                        if reader.read_line(&mut line).unwrap() == 0 {
+                            return sent;
+                        }
+                    }
+                } else if reader.read_line(&mut line).unwrap() == 0 {
+                    return sent;
+                }
+                in_data = line == "DATA\r\n" && reply.starts_with("354");
+                sent.push(line);
+                stream.write_all(reply.as_bytes()).unwrap();
+            }
+            sent
+        });
+        (server, handle)
+    }
+
+    fn smtp(server: String) -> Smtp {
+        Smtp {
+            server,
+            from: "bot@x.org".into(),
+            to: vec!["a@x.org".into(), "b@x.org".into()],
+        }
+    }
+
+    #[test]
+    fn sends_a_mail() {
+        let (server, handle) = serve(vec![
+            "220 hello\r\n",
+            "250-x.org\r\n250-8BITMIME\r\n250 SIZE 1000\r\n",
+            "250 ok\r\n",
+            "250 ok\r\n",
+            "251 forwarding\r\n",
+            "354 go ahead\r\n",
+            "250 queued\r\n",
+            "221 bye\r\n",
+        ]);
+        let body = "First line\n.hidden\n.\nlast";
+        send(&smtp(server), "Digest", Format::Text, body).unwrap();
+
+        let sent = handle.join().unwrap();
+        assert_eq!(
+            sent[..5],
+            [
+                "EHLO thw-subscriber\r\n",
+                "MAIL FROM:<bot@x.org>\r\n",
+                "RCPT TO:<a@x.org>\r\n",
+                "RCPT TO:<b@x.org>\r\n",
+                "DATA\r\n",
+            ]
+        );
+        let message = &sent[5];
+        assert!(
+            message.starts_with("From: bot@x.org\r\nTo: a@x.org, b@x.org\r\nSubject: Digest\r\n")
+        );
+        assert!(message.contains("Content-Type: text/plain; charset=utf-8\r\n"));
+        assert!(message.ends_with("\r\n\r\nFirst line\r\n..hidden\r\n..\r\nlast\r\n.\r\n"));
+        assert_eq!(sent[6], "QUIT\r\n");
+        assert_eq!(sent.len(), 7);
+    }
+
+    #[test]
+    fn fails_on_a_refused_recipient() {
+        let (server, handle) = serve(vec![
+            "220 hello\r\n",
+            "250 x.org\r\n",
+            "250 ok\r\n",
+            "550 no such user\r\n",
+        ]);
+        let e = send(&smtp(server), "Digest", Format::Html, "body").unwrap_err();
+        assert_eq!(e.to_string(), "the SMTP server said 550 no such user");
+        assert_eq!(handle.join().unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,311 @@
+use select::document::Document;
+use select::node::Node;
+use select::predicate::{Attr, Class, Name, Predicate};
+
+use std::collections::HashMap;
+use std::iter;
+
+use crate::demo::ThwDatum;
+use crate::feed::{self, text, FeedError, FeedSource, Post, PostSource, REQUEST_TIMEOUT};
+
+/// Scrapes the "find new posts" page of the Hive Workshop, following its
+/// pagination up to `max_pages` deep.
+pub struct HiveSource {
+    base_url: String,
+    max_pages: usize,
+    /// The threads found by the latest crawl, by key.
+    known: HashMap<String, ThwDatum>,
+}
+
+impl HiveSource {
+    pub fn new(base_url: String, max_pages: usize) -> HiveSource {
+        HiveSource {
+            base_url,
+            max_pages,
+            known: HashMap::new(),
+        }
+    }
+
+    pub fn parse(document: &Document) -> Result<Vec<ThwDatum>, FeedError> {
+        document
+            .find(Class("titleText"))
+            .map(|node| {
+                let title = node
+                    .find(Class("title").descendant(Class("PreviewTooltip")))
+                    .next()
+                    .ok_or_else(|| FeedError::Parse("missing title".into()))?;
+                let second_row = node
+                    .find(Class("secondRow").descendant(Class("forumLink")))
+                    .next()
+                    .ok_or_else(|| FeedError::Parse("missing forum".into()))?;
+                let href = title
+                    .attr("href")
+                    .ok_or_else(|| FeedError::Parse("title didn't have href".into()))?;
+
+                // Everything else is optional, and lives further out in the
+                // thread's row.
+                let item = iter::successors(node.parent(), |node| node.parent())
+                    .find(|node| node.is(Class("discussionListItem")))
+                    .unwrap_or(node);
+
+                Ok(ThwDatum {
+                    title: title.text(),
+                    forum: second_row.text(),
+                    href: href.into(),
+                    thread_id: thread_id(href),
+                    starter: text_of(item, Class("posterDate").descendant(Class("username"))),
+                    last_poster: text_of(item, Class("lastPostInfo").descendant(Class("username"))),
+                    last_post_at: item
+                        .find(Class("lastPost").descendant(Attr("data-time", ())))
+                        .next()
+                        .and_then(|node| node.attr("data-time"))
+                        .and_then(|time| time.parse().ok()),
+                    replies: count_of(item, Name("dl").and(Class("major")).descendant(Name("dd"))),
+                    views: count_of(item, Name("dl").and(Class("minor")).descendant(Name("dd"))),
+                })
+            })
+            .collect()
+    }
+
+    /// The href of the "Next >" link in the page navigation, if any.
+    pub fn next_page(document: &Document) -> Option<String> {
+        document
+            .find(Class("PageNav").descendant(Name("a")))
+            .find(|a| a.text().trim_start().starts_with("Next"))
+            .or_else(|| document.find(Class("pageNav-jump--next")).next())
+            .and_then(|a| a.attr("href"))
+            .map(String::from)
+    }
+
+    /// The text and author of the last post on a thread page.
+    pub fn parse_latest_post(document: &Document) -> Result<Post, FeedError> {
+        let message = document
+            .find(Class("message"))
+            .filter(|node| node.find(Class("messageText")).next().is_some())
+            .last();
+        let body = match message {
+            Some(message) => message.find(Class("messageText")).next(),
+            None => document.find(Class("bbWrapper")).last(),
+        }
+        .ok_or_else(|| FeedError::Parse("missing post".into()))?;
+
+        Ok(Post {
+            author: message
+                .and_then(|message| message.attr("data-author"))
+                .map(String::from),
+            text: text::to_text(body),
+        })
+    }
+}
+
+/// The trimmed text of the first match under `node`, unless it's blank.
+fn text_of<P: Predicate>(node: Node, predicate: P) -> Option<String> {
+    node.find(predicate)
+        .next()
+        .map(|found| found.text().trim().to_string())
+        .filter(|text| !text.is_empty())
+}
+
+/// A number such as "1,234" from the first match under `node`.
+fn count_of<P: Predicate>(node: Node, predicate: P) -> Option<u64> {
+    text_of(node, predicate).and_then(|text| text.replace(',', "").parse().ok())
+}
+
+/// Where the pagination link `href` on the page at `current` leads. Links
+/// that are only a query, such as `?page=3`, stay on the same page.
+fn page_url(base_url: &str, current: &str, href: &str) -> String {
+    if href.starts_with('?') {
+        let path = current.split('?').next().unwrap_or(current);
+        format!("{}{}", path, href)
+    } else {
+        feed::url(base_url, href)
+    }
+}
+
+/// `href` up to and including the thread's slug, e.g. `threads/some-title.123`.
+fn thread_root(href: &str) -> Option<&str> {
+    let start = href.find("threads/")? + "threads/".len();
+    let end = href[start..]
+        .find('/')
+        .map_or(href.len(), |end| start + end);
+    Some(&href[..end]).filter(|_| end > start)
+}
+
+/// The id at the end of the thread's slug.
+pub fn thread_id(href: &str) -> Option<u64> {
+    let root = thread_root(href)?;
+    let slug = &root[root.rfind('/')? + 1..];
+    slug[slug.rfind('.').map_or(0, |dot| dot + 1)..]
+        .parse()
+        .ok()
+}
+
+fn get(url: &str) -> Result<String, FeedError> {
+    let response = ureq::get(url).timeout(REQUEST_TIMEOUT).call();
+    if let Some(e) = response.synthetic_error() {
+        return Err(FeedError::Network(e.to_string()));
+    }
+    if !response.ok() {
+        return Err(FeedError::Network(format!(
+            "{} {}",
+            response.status(),
+            response.status_text()
+        )));
+    }
+    response
+        .into_string()
+        .map_err(|e| FeedError::Network(e.to_string()))
+}
+
+impl FeedSource for HiveSource {
+    fn fetch(&mut self) -> Result<Vec<ThwDatum>, FeedError> {
+        let mut url = feed::url(&self.base_url, "find-new/posts");
+        let mut data: Vec<ThwDatum> = vec![];
+
+        for page in 0..self.max_pages {
+            // Only the first page is essential; a failure further in just cuts
+            // this crawl short.
+            let body = match get(&url) {
+                Ok(body) => body,
+                Err(e) if page == 0 => return Err(e),
+                Err(_) => break,
+            };
+            let document = Document::from(&body[..]);
+            let found = match HiveSource::parse(&document) {
+                Ok(found) => found,
+                Err(e) if page == 0 => return Err(e),
+                Err(_) => break,
+            };
+
+            let all_known = found
+                .iter()
+                .all(|datum| match self.known.get(&datum.key()) {
+                    Some(known) => !datum.has_replies_since(known),
+                    None => false,
+                });
+            for datum in found {
+                // Threads can move down a page between two requests.
+                if !data.contains(&datum) {
+                    data.push(datum);
+                }
+            }
+            if all_known {
+                break;
+            }
+
+            match HiveSource::next_page(&document) {
+                Some(href) => url = page_url(&self.base_url, &url, &href),
+                None => break,
+            }
+        }
+
+        // Older threads fall off the end of the feed; only what's near its
+        // front matters for deciding where to stop next time.
+        self.known = data
+            .iter()
+            .map(|datum| (datum.key(), datum.clone()))
+            .collect();
+        Ok(data)
+    }
+}
+
+impl PostSource for HiveSource {
+    fn latest_post(&mut self, href: &str) -> Result<Post, FeedError> {
+        // ".../threads/some-title.1234/unread" and friends all live under the
+        // same root, whose "latest" page holds the newest post.
+        let root = thread_root(href).unwrap_or_else(|| href.trim_end_matches('/'));
+        let body = get(&feed::url(&self.base_url, &format!("{}/latest", root)))?;
+        HiveSource::parse_latest_post(&Document::from(&body[..]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIND_NEW: &str = include_str!("fixtures/find-new.html");
+    const THREAD: &str = include_str!("fixtures/thread.html");
+
+    #[test]
+    fn parses_threads_and_their_details() {
+        let data = HiveSource::parse(&Document::from(FIND_NEW)).unwrap();
+        assert_eq!(data.len(), 3);
+
+        let spell = &data[0];
+        assert_eq!(spell.title, "A spell [Solved]");
+        assert_eq!(spell.forum, "Triggers & Scripts");
+        assert_eq!(spell.href, "threads/a-spell.101/unread");
+        assert_eq!(spell.thread_id, Some(101));
+        assert_eq!(spell.starter.as_deref(), Some("Bob"));
+        assert_eq!(spell.last_poster.as_deref(), Some("Alice"));
+        assert_eq!(spell.last_post_at, Some(1_600_000_000));
+        assert_eq!(spell.replies, Some(3));
+        assert_eq!(spell.views, Some(1234));
+
+        // Rows without stats still parse.
+        let lib = &data[2];
+        assert_eq!(lib.title, "Wurst lib");
+        assert_eq!(lib.starter, None);
+        assert_eq!(lib.replies, None);
+    }
+
+    #[test]
+    fn rows_without_a_forum_are_an_error() {
+        let html = r#"<div class="titleText"><h3 class="title">
+            <a href="threads/x.1/" class="PreviewTooltip">X</a></h3></div>"#;
+        assert!(matches!(
+            HiveSource::parse(&Document::from(html)),
+            Err(FeedError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn finds_the_next_page() {
+        assert_eq!(
+            HiveSource::next_page(&Document::from(FIND_NEW)).as_deref(),
+            Some("find-new/1/posts?page=2")
+        );
+        let xf2 = r#"<a class="pageNav-jump pageNav-jump--next" href="?page=3">Next</a>"#;
+        assert_eq!(
+            HiveSource::next_page(&Document::from(xf2)).as_deref(),
+            Some("?page=3")
+        );
+        assert_eq!(HiveSource::next_page(&Document::from("<p></p>")), None);
+    }
+
+    #[test]
+    fn resolves_pagination_links() {
+        let base = "https://x.org/";
+        let current = "https://x.org/find-new/1/posts?page=2";
+        assert_eq!(
+            page_url(base, current, "?page=3"),
+            "https://x.org/find-new/1/posts?page=3"
+        );
+        assert_eq!(
+            page_url(base, current, "find-new/1/posts?page=3"),
+            "https://x.org/find-new/1/posts?page=3"
+        );
+    }
+
+    #[test]
+    fn parses_the_latest_post() {
+        let post = HiveSource::parse_latest_post(&Document::from(THREAD)).unwrap();
+        assert_eq!(post.author.as_deref(), Some("Alice"));
+        assert!(post
+            .text
+            .starts_with("> Bob said:\n> How do I make a spell?\nLike this:"));
+    }
+
+    #[test]
+    fn thread_ids() {
+        assert_eq!(thread_id("threads/a-spell.101/unread"), Some(101));
+        assert_eq!(thread_id("/threads/a-spell.101"), Some(101));
+        assert_eq!(
+            thread_id("https://www.hiveworkshop.com/threads/v1-2.3.456/page-2"),
+            Some(456)
+        );
+        assert_eq!(thread_id("threads/789/"), Some(789));
+        assert_eq!(thread_id("threads/no-id/"), None);
+        assert_eq!(thread_id("forums/maps.6/"), None);
+    }
+}
@@ -0,0 +1,190 @@
+mod hive;
+mod text;
+
+pub use hive::{thread_id, HiveSource};
+
+use crate::demo::ThwDatum;
+
+use std::error::Error;
+use std::fmt;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// How long any one request to the forum, or a webhook, may take.
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Delay before the first retry of a failed fetch, doubled on each failure.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(64);
+
+#[derive(Debug)]
+pub enum FeedError {
+    /// The request failed or the server answered with an error status.
+    Network(String),
+    /// The page was fetched but didn't look the way we expected.
+    Parse(String),
+}
+
+impl fmt::Display for FeedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FeedError::Network(msg) => write!(f, "network error: {}", msg),
+            FeedError::Parse(msg) => write!(f, "parse error: {}", msg),
+        }
+    }
+}
+
+impl Error for FeedError {}
+
+/// Joins a site-relative `path` onto `base_url`, tolerating a trailing or
+/// leading slash on either side. Absolute URLs are returned as they are.
+pub fn url(base_url: &str, path: &str) -> String {
+    if path.starts_with("http://") || path.starts_with("https://") {
+        return path.into();
+    }
+    format!(
+        "{}/{}",
+        base_url.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    )
+}
+
+/// The most recent post in a thread, as plain text.
+#[derive(Clone, Debug)]
+pub struct Post {
+    pub author: Option<String>,
+    pub text: String,
+}
+
+/// Something that can produce the current list of new threads, newest first.
+pub trait FeedSource {
+    fn fetch(&mut self) -> Result<Vec<ThwDatum>, FeedError>;
+}
+
+/// Something that can look up the posts in a thread, for the preview pane.
+pub trait PostSource {
+    /// The latest post in the thread at `href`.
+    fn latest_post(&mut self, href: &str) -> Result<Post, FeedError>;
+}
+
+/// Spawns the refresh thread: every unit received on `refresh_rx` triggers a
+/// fetch from `source`, whose results are sent as one batch, oldest first, on
+/// `results_tx`.
+/// Failures are sent too, and the fetch is retried with exponential backoff
+/// until it succeeds. The thread exits once either channel is closed.
+pub fn spawn<S>(
+    mut source: S,
+    refresh_rx: mpsc::Receiver<()>,
+    results_tx: mpsc::Sender<Result<Vec<ThwDatum>, FeedError>>,
+) -> thread::JoinHandle<()>
+where
+    S: FeedSource + Send + 'static,
+{
+    thread::spawn(move || {
+        while refresh_rx.recv().is_ok() {
+            let mut backoff = INITIAL_BACKOFF;
+            loop {
+                match source.fetch() {
+                    Ok(mut data) => {
+                        data.reverse();
+                        if results_tx.send(Ok(data)).is_err() {
+                            return;
+                        }
+                        break;
+                    }
+                    Err(e) => {
+                        if results_tx.send(Err(e)).is_err() {
+                            return;
+                        }
+                        thread::sleep(backoff);
+                        backoff = (backoff * 2).min(MAX_BACKOFF);
+                    }
+                }
+            }
+
+            // Refreshes requested while we were retrying are already satisfied.
+            while refresh_rx.try_recv().is_ok() {}
+        }
+    })
+}
+
+/// Spawns the preview thread: every href received on `request_rx` is answered
+/// on `response_tx` with the latest post of that thread. The thread exits once
+/// either channel is closed.
+pub fn spawn_previews<S>(
+    mut source: S,
+    request_rx: mpsc::Receiver<String>,
+    response_tx: mpsc::Sender<(String, Result<Post, FeedError>)>,
+) -> thread::JoinHandle<()>
+where
+    S: PostSource + Send + 'static,
+{
+    thread::spawn(move || {
+        while let Ok(href) = request_rx.recv() {
+            let post = source.latest_post(&href);
+            if response_tx.send((href, post)).is_err() {
+                return;
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hands out canned fetches, one per refresh.
+    struct MockSource {
+        fetches: Vec<Result<Vec<ThwDatum>, FeedError>>,
+    }
+
+    impl FeedSource for MockSource {
+        fn fetch(&mut self) -> Result<Vec<ThwDatum>, FeedError> {
+            if self.fetches.is_empty() {
+                Ok(vec![])
+            } else {
+                self.fetches.remove(0)
+            }
+        }
+    }
+
+    #[test]
+    fn absolute_urls_are_kept() {
+        assert_eq!(
+            url("https://x.org/", "/threads/a.1/"),
+            "https://x.org/threads/a.1/"
+        );
+        assert_eq!(
+            url("https://x.org", "threads/a.1/"),
+            "https://x.org/threads/a.1/"
+        );
+        assert_eq!(url("https://x.org", "https://y.org/a"), "https://y.org/a");
+    }
+
+    #[test]
+    fn sends_each_fetch_as_a_batch_oldest_first() {
+        let newest = ThwDatum::bare("B", "Maps", "threads/b.2/");
+        let oldest = ThwDatum::bare("A", "Maps", "threads/a.1/");
+        let source = MockSource {
+            fetches: vec![
+                Err(FeedError::Network("down".into())),
+                Ok(vec![newest, oldest]),
+            ],
+        };
+        let (refresh_tx, refresh_rx) = mpsc::channel();
+        let (results_tx, results_rx) = mpsc::channel();
+        let handle = spawn(source, refresh_rx, results_tx);
+
+        refresh_tx.send(()).unwrap();
+        // The failure is reported, then retried without another refresh.
+        assert!(matches!(results_rx.recv(), Ok(Err(FeedError::Network(_)))));
+        let titles: Vec<String> = match results_rx.recv() {
+            Ok(Ok(data)) => data.into_iter().map(|datum| datum.title).collect(),
+            _ => panic!("expected a batch"),
+        };
+        assert_eq!(titles, ["A", "B"]);
+
+        drop(refresh_tx);
+        handle.join().unwrap();
+    }
+}
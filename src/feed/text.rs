@@ -0,0 +1,204 @@
+use select::node::Node;
+use select::predicate::Class;
+
+/// Renders the HTML of a post as plain text: quotes are prefixed with `> `,
+/// list items with `- ` or their number, code blocks are indented and links
+/// are followed by their target.
+pub fn to_text(node: Node) -> String {
+    let mut out = String::new();
+    render_children(node, &mut out);
+    tidy(&out)
+}
+
+fn render_children(node: Node, out: &mut String) {
+    for child in node.children() {
+        render(child, out);
+    }
+}
+
+fn render(node: Node, out: &mut String) {
+    if let Some(text) = node.as_text() {
+        push_collapsed(out, text);
+        return;
+    }
+
+    if node.is(Class("quoteExpand")) {
+        return;
+    }
+    // The quote's own blockquote, inside the one prefixed below.
+    if node.is(Class("quoteContainer")) {
+        render_children(node, out);
+        return;
+    }
+    if node.is(Class("bbCodeQuote")) || node.is(Class("bbCodeBlock--quote")) {
+        block(out, &prefixed(&inner(node), "> "));
+        return;
+    }
+    if node.is(Class("bbCodeCode")) || node.is(Class("bbCodeBlock--code")) {
+        block(out, &prefixed(node.text().trim_matches('\n'), "    "));
+        return;
+    }
+
+    match node.name() {
+        Some("br") => out.push('\n'),
+        Some("script") | Some("style") => {}
+        Some("blockquote") => block(out, &prefixed(&inner(node), "> ")),
+        Some("pre") => block(out, &prefixed(node.text().trim_matches('\n'), "    ")),
+        Some("ul") | Some("ol") => {
+            let ordered = node.name() == Some("ol");
+            let items = node
+                .children()
+                .filter(|child| child.name() == Some("li"))
+                .enumerate()
+                .map(|(i, item)| {
+                    let marker = if ordered {
+                        format!("{}. ", i + 1)
+                    } else {
+                        "- ".to_string()
+                    };
+                    let indent = " ".repeat(marker.len());
+                    let text = prefixed(&inner(item), &indent);
+                    format!("{}{}", marker, &text[indent.len().min(text.len())..])
+                })
+                .collect::<Vec<_>>();
+            block(out, &items.join("\n"));
+        }
+        Some("a") => {
+            let text = inner(node);
+            out.push_str(&text);
+            match node.attr("href") {
+                Some(href) if !href.starts_with('#') && href != text => {
+                    out.push_str(" <");
+                    out.push_str(href);
+                    out.push('>');
+                }
+                _ => {}
+            }
+        }
+        Some("img") => match node.attr("alt") {
+            Some(alt) if !alt.is_empty() => out.push_str(alt),
+            _ => out.push_str("[image]"),
+        },
+        Some("p") | Some("div") | Some("h1") | Some("h2") | Some("h3") | Some("h4") => {
+            block(out, &inner(node))
+        }
+        _ => render_children(node, out),
+    }
+}
+
+/// The tidied text of `node`'s children.
+fn inner(node: Node) -> String {
+    let mut out = String::new();
+    render_children(node, &mut out);
+    tidy(&out)
+}
+
+/// Appends `text` as a paragraph of its own.
+fn block(out: &mut String, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+    out.push_str(text);
+    out.push('\n');
+}
+
+fn prefixed(text: &str, prefix: &str) -> String {
+    text.lines()
+        .map(|line| format!("{}{}", prefix, line).trim_end().to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Appends `text` with runs of whitespace collapsed to a single space, the
+/// way a browser would lay it out.
+fn push_collapsed(out: &mut String, text: &str) {
+    for (i, word) in text.split_whitespace().enumerate() {
+        let at_line_start = out.is_empty() || out.ends_with('\n');
+        if (i > 0 || text.starts_with(char::is_whitespace)) && !at_line_start && !out.ends_with(' ')
+        {
+            out.push(' ');
+        }
+        out.push_str(word);
+    }
+    if text.ends_with(char::is_whitespace)
+        && !(out.is_empty() || out.ends_with('\n') || out.ends_with(' '))
+    {
+        out.push(' ');
+    }
+}
+
+/// Trims trailing spaces and squeezes blank lines down to one.
+fn tidy(text: &str) -> String {
+    let mut lines: Vec<&str> = vec![];
+    for line in text.lines().map(str::trim_end) {
+        if line.trim().is_empty() && lines.last().is_none_or(|last| last.trim().is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+    while lines.last().is_some_and(|last| last.trim().is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use select::document::Document;
+
+    fn text(html: &str) -> String {
+        let document = Document::from(html);
+        let body = document
+            .find(select::predicate::Name("body"))
+            .next()
+            .unwrap();
+        to_text(body)
+    }
+
+    #[test]
+    fn renders_a_post() {
+        let document = Document::from(include_str!("fixtures/thread.html"));
+        let post = document.find(Class("messageText")).last().unwrap();
+        assert_eq!(
+            to_text(post),
+            "> Bob said:\n\
+             > How do I make a spell?\n\
+             Like this:\n\
+             \u{20}   function onCast takes nothing returns nothing\n\
+             \u{20}       call BJDebugMsg(\"hi\")\n\
+             \u{20}   endfunction\n\
+             1. Make a trigger\n\
+             2. Paste the code <https://example.com/code>\n\
+             - :)\n\
+             - [image]"
+        );
+    }
+
+    #[test]
+    fn collapses_whitespace_and_blank_lines() {
+        assert_eq!(
+            text("<p>  one\n  two  </p><p></p><br><br><br><p>three</p>"),
+            "one two\n\nthree"
+        );
+    }
+
+    #[test]
+    fn nests_quotes() {
+        assert_eq!(
+            text("<blockquote>outer<blockquote>inner</blockquote></blockquote>"),
+            "> outer\n> > inner"
+        );
+    }
+
+    #[test]
+    fn leaves_out_links_to_themselves_and_anchors() {
+        assert_eq!(
+            text(r##"<a href="https://x.org">https://x.org</a> <a href="#top">top</a>"##),
+            "https://x.org top"
+        );
+    }
+}
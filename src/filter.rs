@@ -0,0 +1,335 @@
+use crate::demo::ThwDatum;
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use std::cell::OnceCell;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Blocked,
+    Allowed,
+}
+
+impl Status {
+    pub fn toggled(self) -> Status {
+        match self {
+            Status::Blocked => Status::Allowed,
+            Status::Allowed => Status::Blocked,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Status::Blocked => "Blocked",
+            Status::Allowed => "Allowed",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ForumFilter {
+    pub forum: String,
+    pub status: Status,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Every forum is shown unless it's blocked.
+    #[default]
+    Blocklist,
+    /// Only allowed forums are shown.
+    Allowlist,
+}
+
+impl Mode {
+    pub fn toggled(self) -> Mode {
+        match self {
+            Mode::Blocklist => Mode::Allowlist,
+            Mode::Allowlist => Mode::Blocklist,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Mode::Blocklist => "blocklist",
+            Mode::Allowlist => "allowlist",
+        }
+    }
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Mode, String> {
+        match s {
+            "blocklist" => Ok(Mode::Blocklist),
+            "allowlist" => Ok(Mode::Allowlist),
+            _ => Err(format!(
+                "unknown mode {:?}, expected blocklist or allowlist",
+                s
+            )),
+        }
+    }
+}
+
+/// What a title rule does with the threads it matches.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    /// Once there is any include rule, only matching threads are shown.
+    Include,
+    Exclude,
+}
+
+impl Action {
+    pub fn toggled(self) -> Action {
+        match self {
+            Action::Include => Action::Exclude,
+            Action::Exclude => Action::Include,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Include => "Include",
+            Action::Exclude => "Exclude",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Matcher {
+    /// Case-insensitive substring.
+    #[default]
+    Keyword,
+    /// Case-insensitive regular expression.
+    Regex,
+}
+
+impl Matcher {
+    pub fn toggled(self) -> Matcher {
+        match self {
+            Matcher::Keyword => Matcher::Regex,
+            Matcher::Regex => Matcher::Keyword,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Matcher::Keyword => "Keyword",
+            Matcher::Regex => "Regex",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TitleRule {
+    pub pattern: String,
+    #[serde(default)]
+    pub matcher: Matcher,
+    pub action: Action,
+    /// The compiled pattern, or `None` if it isn't a valid regex.
+    #[serde(skip)]
+    regex: OnceCell<Option<Regex>>,
+}
+
+impl TitleRule {
+    pub fn new(pattern: String, matcher: Matcher, action: Action) -> TitleRule {
+        TitleRule {
+            pattern,
+            matcher,
+            action,
+            regex: OnceCell::new(),
+        }
+    }
+
+    /// Checks that the pattern compiles, for rules that are regexes.
+    pub fn validate(&self) -> Result<(), regex::Error> {
+        match self.matcher {
+            Matcher::Keyword => Ok(()),
+            Matcher::Regex => self.compile().map(|_| ()),
+        }
+    }
+
+    fn compile(&self) -> Result<Regex, regex::Error> {
+        RegexBuilder::new(&self.pattern)
+            .case_insensitive(true)
+            .build()
+    }
+
+    pub fn matches(&self, title: &str) -> bool {
+        match self.matcher {
+            Matcher::Keyword => title.to_lowercase().contains(&self.pattern.to_lowercase()),
+            Matcher::Regex => self
+                .regex
+                .get_or_init(|| self.compile().ok())
+                .as_ref()
+                .is_some_and(|regex| regex.is_match(title)),
+        }
+    }
+}
+
+/// Decides which threads make it into the topic list.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Filters {
+    pub mode: Mode,
+    /// How forums missing from `forums` are treated in allowlist mode.
+    pub unknown_forums: Status,
+    // Empty lists would be written as plain values, which TOML doesn't allow
+    // after the tables of the other list.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub forums: Vec<ForumFilter>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub titles: Vec<TitleRule>,
+}
+
+impl Default for Filters {
+    fn default() -> Filters {
+        Filters {
+            mode: Mode::Blocklist,
+            unknown_forums: Status::Blocked,
+            forums: vec![],
+            titles: vec![],
+        }
+    }
+}
+
+impl Filters {
+    /// Blocks every forum in `forums`.
+    pub fn blocking<I>(forums: I) -> Filters
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        Filters {
+            forums: forums
+                .into_iter()
+                .map(|forum| ForumFilter {
+                    forum: forum.into(),
+                    status: Status::Blocked,
+                })
+                .collect(),
+            ..Filters::default()
+        }
+    }
+
+    pub fn allows(&self, datum: &ThwDatum) -> bool {
+        let listed = self
+            .forums
+            .iter()
+            .find(|filter| filter.forum == datum.forum)
+            .map(|filter| filter.status);
+        let status = match (self.mode, listed) {
+            (_, Some(status)) => status,
+            (Mode::Blocklist, None) => Status::Allowed,
+            (Mode::Allowlist, None) => self.unknown_forums,
+        };
+        if status == Status::Blocked {
+            return false;
+        }
+
+        let mut includes = self
+            .titles
+            .iter()
+            .filter(|rule| rule.action == Action::Include)
+            .peekable();
+        let included = includes.peek().is_none() || includes.any(|rule| rule.matches(&datum.title));
+        let excluded = self
+            .titles
+            .iter()
+            .filter(|rule| rule.action == Action::Exclude)
+            .any(|rule| rule.matches(&datum.title));
+        included && !excluded
+    }
+
+    /// Adds `forum` as blocked, unless it's already listed.
+    pub fn add(&mut self, forum: String) {
+        if !self.forums.iter().any(|filter| filter.forum == forum) {
+            self.forums.push(ForumFilter {
+                forum,
+                status: Status::Blocked,
+            });
+        }
+    }
+
+    /// Sets the status of `forum`, adding it if it isn't listed yet.
+    pub fn set_forum(&mut self, forum: String, status: Status) {
+        match self.forums.iter_mut().find(|filter| filter.forum == forum) {
+            Some(filter) => filter.status = status,
+            None => self.forums.push(ForumFilter { forum, status }),
+        }
+    }
+
+    /// Replaces the rule with the same pattern as `rule`, or adds it.
+    pub fn set_title_rule(&mut self, rule: TitleRule) {
+        match self.titles.iter_mut().find(|r| r.pattern == rule.pattern) {
+            Some(existing) => *existing = rule,
+            None => self.titles.push(rule),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thread(forum: &str, title: &str) -> ThwDatum {
+        ThwDatum::bare(title, forum, "threads/x.1/")
+    }
+
+    fn rule(pattern: &str, matcher: Matcher, action: Action) -> TitleRule {
+        TitleRule::new(pattern.into(), matcher, action)
+    }
+
+    #[test]
+    fn blocklist_hides_only_blocked_forums() {
+        let filters = Filters::blocking(vec!["Maps"]);
+        assert!(!filters.allows(&thread("Maps", "A map")));
+        assert!(filters.allows(&thread("Triggers & Scripts", "A spell")));
+    }
+
+    #[test]
+    fn allowlist_shows_only_allowed_forums() {
+        let mut filters = Filters {
+            mode: Mode::Allowlist,
+            ..Filters::default()
+        };
+        filters.set_forum("Maps".into(), Status::Allowed);
+        filters.set_forum("Off-Topic".into(), Status::Blocked);
+        assert!(filters.allows(&thread("Maps", "A map")));
+        assert!(!filters.allows(&thread("Off-Topic", "Hi")));
+        assert!(!filters.allows(&thread("Models", "A model")));
+
+        filters.unknown_forums = Status::Allowed;
+        assert!(filters.allows(&thread("Models", "A model")));
+        assert!(!filters.allows(&thread("Off-Topic", "Hi")));
+    }
+
+    #[test]
+    fn title_rules() {
+        let mut filters = Filters::default();
+        filters.set_title_rule(rule("SOLVED", Matcher::Keyword, Action::Exclude));
+        assert!(!filters.allows(&thread("Maps", "A map [Solved]")));
+        assert!(filters.allows(&thread("Maps", "A map")));
+
+        // Once there's an include rule, a thread has to match one.
+        filters.set_title_rule(rule(r"^\[(jass|lua)\]", Matcher::Regex, Action::Include));
+        assert!(filters.allows(&thread("Maps", "[Lua] A system")));
+        assert!(!filters.allows(&thread("Maps", "A map")));
+        assert!(!filters.allows(&thread("Maps", "[JASS] A spell [solved]")));
+    }
+
+    #[test]
+    fn invalid_regexes_match_nothing() {
+        let bad = rule("([", Matcher::Regex, Action::Exclude);
+        assert!(bad.validate().is_err());
+        assert!(!bad.matches("(["));
+        assert!(rule("([", Matcher::Keyword, Action::Exclude).matches("a (["));
+    }
+}
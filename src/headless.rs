@@ -0,0 +1,136 @@
+use crate::config::Config;
+use crate::demo::ThwDatum;
+use crate::digest::Queue;
+use crate::feed::{self, FeedSource, HiveSource};
+use crate::history::{History, Sighting};
+use crate::notify;
+use crate::output::{self, Format};
+
+use std::error::Error;
+use std::io;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// Exit status of `once` when the fetch failed.
+pub const EXIT_FETCH_FAILED: i32 = 1;
+/// Exit status of `once` when every thread that passes the filters was already
+/// in the history.
+pub const EXIT_NOTHING_NEW: i32 = 2;
+
+/// Runs the refresh loop without a terminal UI, printing every thread that
+/// passes the filters and wasn't in the history yet as one line on stdout.
+pub fn run(
+    config: Config,
+    base_url: String,
+    pages: usize,
+    mut history: History,
+    mut queue: Queue,
+    format: Format,
+) -> Result<(), Box<dyn Error>> {
+    let (refresh_tx, refresh_rx) = mpsc::channel();
+    let (results_tx, results_rx) = mpsc::channel();
+    let _handle = feed::spawn(
+        HiveSource::new(base_url.clone(), pages),
+        refresh_rx,
+        results_tx,
+    );
+
+    let mut notifiers = notify::from_config(&config);
+    let refresh_interval = Duration::from_secs(config.refresh_interval);
+    let mut next_update = Instant::now();
+    let stdout = io::stdout();
+    output::write_header(&mut stdout.lock(), format)?;
+
+    loop {
+        let now = Instant::now();
+        if now >= next_update {
+            refresh_tx.send(())?;
+            next_update = now + refresh_interval;
+        }
+
+        match results_rx.recv_timeout(next_update - now) {
+            Ok(Ok(data)) => {
+                let mut out = stdout.lock();
+                // The first refresh into an empty history is printed but not
+                // notified about, since everything on the feed is new.
+                let seeding = history.is_empty();
+                let mut notified = 0;
+                let mut held_back = 0;
+                for datum in data {
+                    if history.record(&datum) == Sighting::New && config.filters.allows(&datum) {
+                        output::write_line(&mut out, format, &datum, &base_url)?;
+                        if seeding {
+                            continue;
+                        }
+                        let alert_now = config.notify.alerts_now(&datum);
+                        if !alert_now || notified == notify::MAX_PER_REFRESH {
+                            // Past the limit, threads go to the digest instead.
+                            if alert_now {
+                                held_back += 1;
+                            }
+                            queue.push(datum)?;
+                            continue;
+                        }
+                        notified += 1;
+                        let url = feed::url(&base_url, &datum.href);
+                        for notifier in &mut notifiers {
+                            if let Err(e) = notifier.notify(&datum, &url) {
+                                eprintln!("Notification failed: {}", e);
+                            }
+                        }
+                    }
+                }
+                if held_back > 0 {
+                    eprintln!(
+                        "{} more new threads were held back for the digest",
+                        held_back
+                    );
+                }
+                history.save()?;
+            }
+            Ok(Err(e)) => eprintln!("Refresh failed: {}", e),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                for notifier in &mut notifiers {
+                    notifier.finish();
+                }
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Fetches the new posts once and prints every thread that passes the filters.
+/// Returns the status to exit with.
+pub fn once(
+    config: Config,
+    base_url: String,
+    pages: usize,
+    mut history: History,
+    format: Format,
+) -> Result<i32, Box<dyn Error>> {
+    let data = match HiveSource::new(base_url.clone(), pages).fetch() {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("Fetch failed: {}", e);
+            return Ok(EXIT_FETCH_FAILED);
+        }
+    };
+
+    // Oldest first, the order they were posted in.
+    let mut anything_new = false;
+    let shown: Vec<&ThwDatum> = data
+        .iter()
+        .rev()
+        .filter(|datum| {
+            let new = history.record(datum) == Sighting::New;
+            let allowed = config.filters.allows(datum);
+            anything_new |= new && allowed;
+            allowed
+        })
+        .collect();
+    history.save()?;
+
+    output::write_all(&mut io::stdout().lock(), format, shown, &base_url)?;
+    Ok(if anything_new { 0 } else { EXIT_NOTHING_NEW })
+}
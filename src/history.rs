@@ -0,0 +1,325 @@
+use crate::demo::ThwDatum;
+use crate::feed;
+use crate::lock;
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Threads that haven't shown up on the feed for this long are forgotten.
+const MAX_AGE_SECS: u64 = 90 * 24 * 60 * 60;
+/// Most threads kept, dropping those with the oldest activity first.
+const MAX_ENTRIES: usize = 5000;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Entry {
+    #[serde(flatten)]
+    pub datum: ThwDatum,
+    /// Seconds since the unix epoch.
+    pub first_seen: u64,
+    pub last_seen: u64,
+    #[serde(default)]
+    pub read: bool,
+    /// Whether the thread got replies since it was last read.
+    #[serde(default)]
+    pub bumped: bool,
+}
+
+/// What `History::record` made of a thread.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Sighting {
+    /// Never seen before.
+    New,
+    /// Seen before, but it has replies since.
+    Bumped,
+    Unchanged,
+}
+
+/// What this process changed about an entry since the last save.
+#[derive(Clone, Copy, Default)]
+struct Change {
+    /// It's new or has replies, so it moves to the end.
+    moved: bool,
+    /// Its read state changed.
+    marked: bool,
+}
+
+/// Every thread we've ever been shown, keyed by `ThwDatum::key` and in order
+/// of their latest activity. Backed by a JSON file when given a path, which
+/// other processes may be saving to as well: saving merges our changes into
+/// theirs.
+pub struct History {
+    path: Option<PathBuf>,
+    entries: IndexMap<String, Entry>,
+    changes: HashMap<String, Change>,
+    dirty: bool,
+}
+
+impl History {
+    /// `$XDG_DATA_HOME/thw-subscriber/history.json` or the platform equivalent.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("thw-subscriber").join("history.json"))
+    }
+
+    /// A history that is never written to disk.
+    pub fn in_memory() -> History {
+        History {
+            path: None,
+            entries: IndexMap::new(),
+            changes: HashMap::new(),
+            dirty: false,
+        }
+    }
+
+    /// Reads the history at `path`, which doesn't have to exist yet.
+    pub fn load(path: PathBuf) -> io::Result<History> {
+        let mut history = History {
+            entries: read(&path)?,
+            path: Some(path),
+            changes: HashMap::new(),
+            dirty: false,
+        };
+        history.prune();
+        Ok(history)
+    }
+
+    /// Forgets the threads past `MAX_AGE_SECS` or `MAX_ENTRIES`.
+    fn prune(&mut self) {
+        let len = self.entries.len();
+        let cutoff = now().saturating_sub(MAX_AGE_SECS);
+        self.entries.retain(|_, entry| entry.last_seen >= cutoff);
+        let excess = self.entries.len().saturating_sub(MAX_ENTRIES);
+        self.entries.drain(..excess);
+        self.dirty |= self.entries.len() != len;
+    }
+
+    /// Whether no thread has been seen yet, e.g. on the very first run.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.values()
+    }
+
+    /// Notes that `datum` was just seen, updating what we know about it. A
+    /// thread with new replies moves to the end and is unread again.
+    pub fn record(&mut self, datum: &ThwDatum) -> Sighting {
+        let now = now();
+        let key = datum.key();
+        self.dirty = true;
+        match self.entries.get_mut(&key) {
+            Some(entry) if datum.has_replies_since(&entry.datum) => {
+                entry.datum = datum.clone();
+                entry.last_seen = now;
+                entry.read = false;
+                entry.bumped = true;
+                let (_, entry) = self.entries.shift_remove_entry(&key).unwrap();
+                self.entries.insert(key.clone(), entry);
+                self.changes.entry(key).or_default().moved = true;
+                Sighting::Bumped
+            }
+            Some(entry) => {
+                entry.datum = datum.clone();
+                entry.last_seen = now;
+                self.changes.entry(key).or_default();
+                Sighting::Unchanged
+            }
+            None => {
+                self.changes.entry(key.clone()).or_default().moved = true;
+                self.entries.insert(
+                    key,
+                    Entry {
+                        datum: datum.clone(),
+                        first_seen: now,
+                        last_seen: now,
+                        read: false,
+                        bumped: false,
+                    },
+                );
+                Sighting::New
+            }
+        }
+    }
+
+    pub fn is_read(&self, key: &str) -> bool {
+        self.entries.get(key).is_some_and(|entry| entry.read)
+    }
+
+    pub fn is_bumped(&self, key: &str) -> bool {
+        self.entries.get(key).is_some_and(|entry| entry.bumped)
+    }
+
+    pub fn mark_read(&mut self, key: &str) {
+        if let Some(entry) = self.entries.get_mut(key) {
+            if !entry.read || entry.bumped {
+                entry.read = true;
+                entry.bumped = false;
+                self.changes.entry(key.into()).or_default().marked = true;
+                self.dirty = true;
+            }
+        }
+    }
+
+    pub fn mark_all_read(&mut self) {
+        for (key, entry) in self
+            .entries
+            .iter_mut()
+            .filter(|(_, entry)| !entry.read || entry.bumped)
+        {
+            entry.read = true;
+            entry.bumped = false;
+            self.changes.entry(key.clone()).or_default().marked = true;
+            self.dirty = true;
+        }
+    }
+
+    /// Writes the history back to disk if anything changed since last time,
+    /// on top of whatever other processes saved in the meantime.
+    pub fn save(&mut self) -> io::Result<()> {
+        self.prune();
+        let path = match &self.path {
+            Some(path) if self.dirty => path.clone(),
+            _ => return Ok(()),
+        };
+
+        let _lock = lock::lock(&path)?;
+        let mut merged = read(&path)?;
+        self.merge_into(&mut merged);
+        self.entries = merged;
+        self.prune();
+
+        let entries: Vec<&Entry> = self.entries.values().collect();
+        let json = serde_json::to_string_pretty(&entries)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        lock::write(&path, &json)?;
+
+        self.changes.clear();
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Applies the changes made since the last save to `saved`, the history
+    /// as it is on disk now.
+    fn merge_into(&self, saved: &mut IndexMap<String, Entry>) {
+        for (key, ours) in &self.entries {
+            let change = match self.changes.get(key) {
+                Some(change) => *change,
+                None => continue,
+            };
+            let mut entry = match saved.get(key) {
+                Some(theirs) => {
+                    let mut entry = theirs.clone();
+                    if ours.last_seen >= theirs.last_seen {
+                        entry.datum = ours.datum.clone();
+                        entry.last_seen = ours.last_seen;
+                    }
+                    entry.first_seen = entry.first_seen.min(ours.first_seen);
+                    entry
+                }
+                None => ours.clone(),
+            };
+            if change.moved || change.marked {
+                entry.read = ours.read;
+                entry.bumped = ours.bumped;
+            }
+            // Inserting keeps the position of an entry that's there already.
+            if change.moved {
+                saved.shift_remove(key);
+            }
+            saved.insert(key.clone(), entry);
+        }
+    }
+}
+
+fn read(path: &Path) -> io::Result<IndexMap<String, Entry>> {
+    let entries: Vec<Entry> = match fs::read_to_string(path) {
+        Ok(json) => serde_json::from_str(&json)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+        Err(e) => return Err(e),
+    };
+
+    // Histories from before thread ids were parsed only have the href.
+    Ok(entries
+        .into_iter()
+        .map(|mut entry| {
+            if entry.datum.thread_id.is_none() {
+                entry.datum.thread_id = feed::thread_id(&entry.datum.href);
+            }
+            (entry.datum.key(), entry)
+        })
+        .collect())
+}
+
+/// Seconds since the unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("thw-history-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join("history.json")
+    }
+
+    #[test]
+    fn saves_merge_with_other_processes() {
+        let path = temp_path("merge");
+        let mut tui = History::load(path.clone()).unwrap();
+        let mut watch = History::load(path.clone()).unwrap();
+
+        tui.record(&ThwDatum::bare("A", "Maps", "threads/a.1/"));
+        tui.save().unwrap();
+        watch.record(&ThwDatum::bare("B", "Maps", "threads/b.2/"));
+        watch.save().unwrap();
+        tui.mark_read("1");
+        tui.save().unwrap();
+        // Seeing a thread again doesn't undo it being read elsewhere.
+        watch.record(&ThwDatum::bare("A", "Maps", "threads/a.1/"));
+        watch.save().unwrap();
+
+        let saved = History::load(path.clone()).unwrap();
+        let keys: Vec<String> = saved.entries().map(|entry| entry.datum.key()).collect();
+        assert_eq!(keys, ["1", "2"]);
+        assert!(saved.is_read("1"));
+        assert!(!saved.is_read("2"));
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn forgets_old_threads() {
+        let mut history = History::in_memory();
+        history.record(&ThwDatum::bare("A", "Maps", "threads/a.1/"));
+        history.record(&ThwDatum::bare("B", "Maps", "threads/b.2/"));
+        history.entries.get_mut("1").unwrap().last_seen = 0;
+
+        history.prune();
+        let keys: Vec<String> = history.entries().map(|entry| entry.datum.key()).collect();
+        assert_eq!(keys, ["2"]);
+    }
+
+    #[test]
+    fn keeps_the_latest_threads() {
+        let mut history = History::in_memory();
+        for id in 0..MAX_ENTRIES + 2 {
+            history.record(&ThwDatum::bare("A", "Maps", &format!("threads/a.{}/", id)));
+        }
+
+        history.prune();
+        assert_eq!(history.entries.len(), MAX_ENTRIES);
+        assert_eq!(history.entries.keys().next().map(String::as_str), Some("2"));
+    }
+}
@@ -0,0 +1,30 @@
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::Path;
+
+/// Takes an exclusive lock on `path`, by way of a `.lock` file next to it, so
+/// that several processes can read, change and write it back in turn. The
+/// lock is released when the returned file is dropped.
+pub fn lock(path: &Path) -> io::Result<File> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut name = path.as_os_str().to_owned();
+    name.push(".lock");
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(name)?;
+    file.lock()?;
+    Ok(file)
+}
+
+/// Writes `contents` to `path` by way of a temporary file, so that a crash
+/// never leaves half a file behind.
+pub fn write(path: &Path, contents: &str) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path)
+}
@@ -1,21 +1,38 @@
+mod cli;
+mod commands;
+mod config;
 #[allow(dead_code)]
 mod demo;
+mod digest;
+mod feed;
+mod filter;
+mod headless;
+mod history;
+mod lock;
+mod notify;
+mod output;
+mod sort;
 #[allow(dead_code)]
 mod util;
 
-use crate::demo::{ui, App, ThwDatum};
-use argh::FromArgs;
+use crate::cli::{Cli, Command};
+use crate::config::Config;
+use crate::demo::{ui, App};
+use crate::digest::Queue;
+use crate::feed::HiveSource;
+use crate::history::History;
 use crossterm::{
     event::{self, DisableMouseCapture, Event as CEvent, KeyCode},
     execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    terminal::{
+        disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen, SetTitle,
+    },
 };
-use select::document::Document;
-use select::predicate::{Class, Predicate};
 
 use std::{
     error::Error,
-    io::stdout,
+    io::{stdout, Write},
+    process,
     sync::mpsc,
     thread,
     time::{Duration, Instant},
@@ -27,20 +44,54 @@ enum Event<I> {
     Tick,
 }
 
-/// Crossterm demo
-#[derive(Debug, FromArgs)]
-struct Cli {
-    /// time in ms between two ticks.
-    #[argh(option, default = "250")]
-    tick_rate: u64,
-    /// whether unicode symbols are used to improve the overall look of the app
-    #[argh(option, default = "true")]
-    enhanced_graphics: bool,
-}
-
 fn main() -> Result<(), Box<dyn Error>> {
     let cli: Cli = argh::from_env();
+    let default_command = cli.default_command();
+
+    let config = match cli.config.or_else(Config::default_path) {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
+    let base_url = cli.base_url.unwrap_or_else(|| config.base_url.clone());
+
+    let history_path = cli.history.or_else(History::default_path);
+    let history = match &history_path {
+        Some(path) => History::load(path.clone())?,
+        None => History::in_memory(),
+    };
+    // Threads waiting for the next digest are kept next to the history.
+    let queue = match &history_path {
+        Some(path) => Queue::load(path.with_file_name("digest.json"))?,
+        None => Queue::in_memory(),
+    };
+
+    match cli.command.unwrap_or(default_command) {
+        Command::Tui(args) => run_tui(args, config, base_url, cli.pages, history, queue),
+        Command::Watch(args) => {
+            headless::run(config, base_url, cli.pages, history, queue, args.format)
+        }
+        Command::List(args) => {
+            let status = headless::once(config, base_url, cli.pages, history, args.format)?;
+            process::exit(status);
+        }
+        Command::Export(args) => commands::export(args, &history, &base_url),
+        Command::Filters(args) => commands::filters(args, config),
+        Command::History(args) => commands::history(args, history, &base_url),
+        Command::Digest(args) => {
+            let status = digest::run(args, config, base_url, cli.pages, history, queue)?;
+            process::exit(status);
+        }
+    }
+}
 
+fn run_tui(
+    args: cli::Tui,
+    config: Config,
+    base_url: String,
+    pages: usize,
+    history: History,
+    queue: Queue,
+) -> Result<(), Box<dyn Error>> {
     enable_raw_mode()?;
 
     let mut stdout = stdout();
@@ -54,8 +105,8 @@ fn main() -> Result<(), Box<dyn Error>> {
     // Setup input handling
     let (tx, rx) = mpsc::channel();
 
-    let tick_rate = Duration::from_millis(cli.tick_rate);
-    let handle_in = thread::spawn(move || {
+    let tick_rate = Duration::from_millis(args.tick_rate);
+    thread::spawn(move || {
         let mut last_tick = Instant::now();
         loop {
             // poll for tick rate duration, if no events, sent tick event.
@@ -75,62 +126,52 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     let (refresh_tx, refresh_rx) = mpsc::channel();
     let (results_tx, results_rx) = mpsc::channel();
+    let (preview_tx, preview_rx) = mpsc::channel();
+    let (post_tx, post_rx) = mpsc::channel();
 
     let tx_clone = refresh_tx.clone();
     tx_clone.send(()).expect("Failed to send initial unit");
 
     let mut app = App::new(
         "THW Subscriber",
-        cli.enhanced_graphics,
+        args.enhanced_graphics,
+        base_url.clone(),
+        config,
         refresh_tx,
         results_rx,
+        history,
+        queue,
+        preview_tx,
+        post_rx,
     );
 
-    let handle = thread::spawn(move || loop {
-        match refresh_rx.recv() {
-            Ok(()) => {
-                let body = ureq::get("https://www.hiveworkshop.com/find-new/posts")
-                    .call()
-                    .into_string()
-                    .expect("Failed to fetch");
-                Document::from(&body[..])
-                    .find(Class("titleText"))
-                    .map(|node| {
-                        let title = node
-                            .find(Class("title").descendant(Class("PreviewTooltip")))
-                            .next()
-                            .expect("missing title");
-                        let second_row = node
-                            .find(Class("secondRow").descendant(Class("forumLink")))
-                            .next()
-                            .expect("missing forum");
-
-                        (
-                            title.attr("href").expect("node didn't have href").into(),
-                            title.text(),
-                            second_row.text(),
-                        )
-                    })
-                    .collect::<Vec<_>>()
-                    .into_iter()
-                    .rev()
-                    .for_each(|(href, title, forum)| {
-                        results_tx
-                            .send(ThwDatum { title, forum, href })
-                            .expect("failed to send test datum");
-                    });
-            }
-            Err(e) => panic!("{:?} {:?}", e, e.to_string()),
-        }
-    });
+    feed::spawn(
+        HiveSource::new(base_url.clone(), pages),
+        refresh_rx,
+        results_tx,
+    );
+    feed::spawn_previews(HiveSource::new(base_url, pages), preview_rx, post_tx);
 
     terminal.clear()?;
 
+    let mut window_title = String::new();
     loop {
         terminal.draw(|f| ui::draw(f, &mut app))?;
+        if app.ring_bell {
+            app.ring_bell = false;
+            let backend = terminal.backend_mut();
+            write!(backend, "\x07")?;
+            backend.flush()?;
+        }
+        if let Some(title) = app.window_title().filter(|title| *title != window_title) {
+            execute!(terminal.backend_mut(), SetTitle(&title))?;
+            window_title = title;
+        }
+
         match rx.recv()? {
             Event::Input(event) => match event.code {
-                KeyCode::Char('q') => {
+                code if app.input.is_some() => app.on_input(code),
+                KeyCode::Char(c) if c == app.config.keys.quit => {
                     disable_raw_mode()?;
                     execute!(
                         terminal.backend_mut(),
@@ -141,6 +182,8 @@ fn main() -> Result<(), Box<dyn Error>> {
                     break;
                 }
                 KeyCode::Char(c) => app.on_key(c),
+                KeyCode::Tab => app.on_tab(),
+                KeyCode::Enter => app.on_enter(),
                 KeyCode::Left => app.on_left(),
                 KeyCode::Up => app.on_up(),
                 KeyCode::Right => app.on_right(),
@@ -156,8 +199,22 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
     }
 
-    handle.join().expect("failed to join thread");
-    handle_in.join().expect("failed to join handle-in");
+    // The old title can't be read back, but an empty one puts most terminals
+    // back on their own.
+    if !window_title.is_empty() {
+        execute!(terminal.backend_mut(), SetTitle(""))?;
+    }
+
+    // Whatever was marked since the last tick would be lost otherwise.
+    if let Err(e) = app.history.save() {
+        eprintln!("Failed to save history: {}", e);
+    }
+
+    for notifier in &mut app.notifiers {
+        notifier.finish();
+    }
+    // The input and refresh threads never finish on their own; they go down
+    // with the process.
 
     Ok(())
 }
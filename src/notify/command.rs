@@ -0,0 +1,91 @@
+use crate::demo::ThwDatum;
+use crate::notify::Notifier;
+
+use std::error::Error;
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// Runs a command for every new thread. `{title}`, `{forum}` and `{url}` in
+/// its arguments are replaced with the thread's, which are also passed in the
+/// `THW_TITLE`, `THW_FORUM` and `THW_URL` environment variables.
+pub struct CommandNotifier {
+    words: Vec<String>,
+}
+
+impl CommandNotifier {
+    /// `command` is split on whitespace, like the opener. Returns `None` if
+    /// it's blank.
+    pub fn new(command: &str) -> Option<CommandNotifier> {
+        let words: Vec<String> = command.split_whitespace().map(String::from).collect();
+        if words.is_empty() {
+            None
+        } else {
+            Some(CommandNotifier { words })
+        }
+    }
+}
+
+impl Notifier for CommandNotifier {
+    fn notify(&mut self, datum: &ThwDatum, url: &str) -> Result<(), Box<dyn Error>> {
+        let values = [
+            ("{title}", datum.title.as_str()),
+            ("{forum}", datum.forum.as_str()),
+            ("{url}", url),
+        ];
+        let mut child = Command::new(&self.words[0])
+            .args(self.words[1..].iter().map(|word| fill(word, &values)))
+            .env("THW_TITLE", &datum.title)
+            .env("THW_FORUM", &datum.forum)
+            .env("THW_URL", url)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        // Reap it in the background so a slow command doesn't hold us up.
+        thread::spawn(move || child.wait());
+        Ok(())
+    }
+}
+
+/// `word` with its placeholders replaced, in one pass so that a title that
+/// happens to contain `{url}` stays as it is.
+fn fill(word: &str, values: &[(&str, &str)]) -> String {
+    let mut out = String::new();
+    let mut rest = word;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        match values.iter().find(|(name, _)| rest.starts_with(name)) {
+            Some((name, value)) => {
+                out.push_str(value);
+                rest = &rest[name.len()..];
+            }
+            None => {
+                out.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALUES: [(&str, &str); 2] = [("{title}", "A {url} title"), ("{url}", "https://x.org")];
+
+    #[test]
+    fn fills_placeholders() {
+        assert_eq!(fill("{title}", &VALUES), "A {url} title");
+        assert_eq!(fill("--url={url}&x", &VALUES), "--url=https://x.org&x");
+        assert_eq!(fill("{url}{url}", &VALUES), "https://x.orghttps://x.org");
+    }
+
+    #[test]
+    fn leaves_other_braces_alone() {
+        assert_eq!(fill("{forum} {", &VALUES), "{forum} {");
+        assert_eq!(fill("{{title}}", &VALUES), "{A {url} title}");
+    }
+}
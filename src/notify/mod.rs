@@ -0,0 +1,173 @@
+mod command;
+mod webhook;
+
+pub use command::CommandNotifier;
+pub use webhook::{Webhook, WebhookNotifier};
+
+use crate::config::Config;
+use crate::demo::ThwDatum;
+
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::str::FromStr;
+
+/// Most new threads the notifiers are told about per refresh; any more are
+/// held back for the digest, so that a long gap between refreshes doesn't set
+/// off dozens of commands and webhooks at once.
+pub const MAX_PER_REFRESH: usize = 5;
+
+/// Something that's told about every new thread that passes the filters.
+pub trait Notifier {
+    /// `url` is the absolute link to the thread.
+    fn notify(&mut self, datum: &ThwDatum, url: &str) -> Result<(), Box<dyn Error>>;
+
+    /// Called once before quitting, to wrap up anything still under way.
+    fn finish(&mut self) {}
+}
+
+/// The notifiers set up in `config`.
+pub fn from_config(config: &Config) -> Vec<Box<dyn Notifier>> {
+    let mut notifiers: Vec<Box<dyn Notifier>> = vec![];
+    if let Some(notifier) = config
+        .notify
+        .command
+        .as_deref()
+        .and_then(CommandNotifier::new)
+    {
+        notifiers.push(Box::new(notifier));
+    }
+    if !config.notify.webhooks.is_empty() {
+        let log = config.notify.webhook_log.clone().or_else(|| {
+            dirs::data_dir().map(|dir| dir.join("thw-subscriber").join("webhooks.log"))
+        });
+        notifiers.push(Box::new(WebhookNotifier::new(
+            config.notify.webhooks.clone(),
+            log,
+        )));
+    }
+    notifiers
+}
+
+/// How much a new thread matters. Low ones never alert and wait for the next
+/// digest instead, normal ones alert outside quiet hours, and high ones always
+/// alert.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+/// Gives threads in `forum` whose title contains `keyword` a priority. Either
+/// can be left out.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PriorityRule {
+    pub forum: Option<String>,
+    pub keyword: Option<String>,
+    pub priority: Priority,
+}
+
+impl PriorityRule {
+    pub fn matches(&self, datum: &ThwDatum) -> bool {
+        thread_matches(self.forum.as_deref(), self.keyword.as_deref(), datum)
+    }
+}
+
+/// Whether `datum` is in `forum` and has `keyword` in its title, ignoring
+/// case. A missing forum or keyword matches anything.
+pub fn thread_matches(forum: Option<&str>, keyword: Option<&str>, datum: &ThwDatum) -> bool {
+    forum.is_none_or(|forum| forum == datum.forum)
+        && keyword
+            .is_none_or(|keyword| datum.title.to_lowercase().contains(&keyword.to_lowercase()))
+}
+
+/// A time of day, such as `22:00-07:00`, during which only high priority
+/// threads alert. It may run past midnight.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct QuietHours {
+    pub from: NaiveTime,
+    pub to: NaiveTime,
+}
+
+impl QuietHours {
+    pub fn contains(self, time: NaiveTime) -> bool {
+        if self.from <= self.to {
+            self.from <= time && time < self.to
+        } else {
+            time >= self.from || time < self.to
+        }
+    }
+}
+
+impl FromStr for QuietHours {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<QuietHours, String> {
+        let invalid = || format!("invalid quiet hours {:?}, expected e.g. 22:00-07:00", s);
+        let (from, to) = s.split_once('-').ok_or_else(invalid)?;
+        let time =
+            |time: &str| NaiveTime::parse_from_str(time.trim(), "%H:%M").map_err(|_| invalid());
+        Ok(QuietHours {
+            from: time(from)?,
+            to: time(to)?,
+        })
+    }
+}
+
+impl TryFrom<String> for QuietHours {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<QuietHours, String> {
+        s.parse()
+    }
+}
+
+impl From<QuietHours> for String {
+    fn from(quiet: QuietHours) -> String {
+        format!(
+            "{}-{}",
+            quiet.from.format("%H:%M"),
+            quiet.to.format("%H:%M")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn quiet_hours_within_a_day() {
+        let lunch: QuietHours = "12:00-13:30".parse().unwrap();
+        assert!(lunch.contains(at(12, 0)));
+        assert!(lunch.contains(at(13, 29)));
+        assert!(!lunch.contains(at(13, 30)));
+        assert!(!lunch.contains(at(11, 59)));
+    }
+
+    #[test]
+    fn quiet_hours_past_midnight() {
+        let night: QuietHours = "22:00-07:00".parse().unwrap();
+        assert!(night.contains(at(22, 0)));
+        assert!(night.contains(at(0, 0)));
+        assert!(night.contains(at(6, 59)));
+        assert!(!night.contains(at(7, 0)));
+        assert!(!night.contains(at(21, 59)));
+    }
+
+    #[test]
+    fn invalid_quiet_hours() {
+        assert!("22:00".parse::<QuietHours>().is_err());
+        assert!("22:00-25:00".parse::<QuietHours>().is_err());
+    }
+}
@@ -0,0 +1,432 @@
+use crate::demo::ThwDatum;
+use crate::feed::REQUEST_TIMEOUT;
+use crate::history::now;
+use crate::notify::Notifier;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Deliveries are given up on after this many tries.
+const MAX_ATTEMPTS: u32 = 4;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(2);
+/// Longest we'll honour a `Retry-After` for.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+/// How long quitting waits for a delivery that's under way.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+
+/// The shape of the JSON a webhook is sent.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Payload {
+    Discord,
+    Slack,
+    /// The thread as it's kept in the history, plus its `url`.
+    #[default]
+    Json,
+}
+
+/// A URL that new threads are POSTed to.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Webhook {
+    pub url: String,
+    /// What the delivery log calls it, defaults to the URL's host.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub payload: Payload,
+    /// Only threads from these forums are sent; all of them if empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub forums: Vec<String>,
+}
+
+impl Webhook {
+    fn name(&self) -> &str {
+        match &self.name {
+            Some(name) => name,
+            // Leave out the path, which usually holds the token.
+            None => {
+                let rest = self.url.split("://").nth(1).unwrap_or(&self.url);
+                rest.split('/').next().unwrap_or(rest)
+            }
+        }
+    }
+
+    fn wants(&self, datum: &ThwDatum) -> bool {
+        self.forums.is_empty() || self.forums.contains(&datum.forum)
+    }
+}
+
+struct Delivery {
+    name: String,
+    url: String,
+    body: String,
+    thread: String,
+}
+
+/// POSTs new threads to webhooks, each from a thread of its own so one that's
+/// down doesn't hold up the rest. Failed deliveries are retried with
+/// exponential backoff, and every attempt is appended to a log.
+pub struct WebhookNotifier {
+    webhooks: Vec<(Webhook, mpsc::Sender<Delivery>)>,
+    workers: Vec<thread::JoinHandle<()>>,
+    /// Set on quitting, after which nothing is retried.
+    stopping: Arc<AtomicBool>,
+}
+
+impl WebhookNotifier {
+    pub fn new(webhooks: Vec<Webhook>, log: Option<PathBuf>) -> WebhookNotifier {
+        let stopping = Arc::new(AtomicBool::new(false));
+        let mut workers = vec![];
+        let webhooks = webhooks
+            .into_iter()
+            .map(|webhook| {
+                let (sender, receiver) = mpsc::channel::<Delivery>();
+                let log = log.clone();
+                let stopping = stopping.clone();
+                workers.push(thread::spawn(move || {
+                    for delivery in receiver {
+                        if stopping.load(Ordering::Relaxed) {
+                            delivery.log(log.as_ref(), 0, "dropped on quitting");
+                        } else {
+                            deliver(&delivery, log.as_ref(), &stopping);
+                        }
+                    }
+                }));
+                (webhook, sender)
+            })
+            .collect();
+        WebhookNotifier {
+            webhooks,
+            workers,
+            stopping,
+        }
+    }
+}
+
+impl Notifier for WebhookNotifier {
+    fn notify(&mut self, datum: &ThwDatum, url: &str) -> Result<(), Box<dyn Error>> {
+        for (webhook, sender) in self
+            .webhooks
+            .iter()
+            .filter(|(webhook, _)| webhook.wants(datum))
+        {
+            let delivery = Delivery {
+                name: webhook.name().to_string(),
+                url: webhook.url.clone(),
+                body: payload(webhook.payload, datum, url).to_string(),
+                thread: url.to_string(),
+            };
+            sender
+                .send(delivery)
+                .map_err(|_| "the webhook thread has stopped")?;
+        }
+        Ok(())
+    }
+
+    /// Stops retrying and logs the deliveries that are still waiting as
+    /// dropped, giving one that's under way a few seconds to finish.
+    fn finish(&mut self) {
+        self.stopping.store(true, Ordering::Relaxed);
+        self.webhooks.clear();
+        let deadline = Instant::now() + SHUTDOWN_GRACE;
+        for worker in self.workers.drain(..) {
+            while !worker.is_finished() && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(50));
+            }
+            if worker.is_finished() {
+                let _ = worker.join();
+            }
+        }
+    }
+}
+
+fn payload(payload: Payload, datum: &ThwDatum, url: &str) -> serde_json::Value {
+    match payload {
+        Payload::Discord => {
+            let mut embed = json!({
+                "title": datum.title,
+                "url": url,
+                "description": datum.forum,
+            });
+            if let Some(starter) = &datum.starter {
+                embed["author"] = json!({ "name": starter });
+            }
+            json!({ "username": "THW Subscriber", "embeds": [embed] })
+        }
+        Payload::Slack => json!({
+            "text": format!(
+                "<{}|{}> in {}",
+                url,
+                slack_escape(&datum.title),
+                slack_escape(&datum.forum)
+            ),
+        }),
+        Payload::Json => {
+            let mut value = serde_json::to_value(datum).unwrap_or_else(|_| json!({}));
+            value["url"] = json!(url);
+            value
+        }
+    }
+}
+
+/// Escapes the characters Slack treats as markup.
+fn slack_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+impl Delivery {
+    fn log(&self, log: Option<&PathBuf>, attempt: u32, outcome: &str) {
+        write_log(
+            log,
+            &format!(
+                "{} {} attempt {}/{} {} {}",
+                now(),
+                self.name,
+                attempt,
+                MAX_ATTEMPTS,
+                self.thread,
+                outcome
+            ),
+        );
+    }
+}
+
+/// Tries to deliver until it works, the hook refuses it, we run out of
+/// attempts, or we're quitting.
+fn deliver(delivery: &Delivery, log: Option<&PathBuf>, stopping: &AtomicBool) {
+    let mut backoff = INITIAL_BACKOFF;
+    for attempt in 1..=MAX_ATTEMPTS {
+        let response = ureq::post(&delivery.url)
+            .timeout(REQUEST_TIMEOUT)
+            .set("Content-Type", "application/json")
+            .send_string(&delivery.body);
+
+        let (outcome, retry) = if let Some(e) = response.synthetic_error() {
+            (format!("failed: {}", e), true)
+        } else if response.ok() {
+            (format!("delivered: {}", response.status()), false)
+        } else {
+            // Rate limits and server errors are worth another go; anything
+            // else will be refused again.
+            let status = response.status();
+            if let Some(secs) = response
+                .header("Retry-After")
+                .and_then(|secs| secs.trim().parse().ok())
+            {
+                backoff = backoff.max(Duration::from_secs(secs).min(MAX_RETRY_AFTER));
+            }
+            (
+                format!("failed: {} {}", status, response.status_text()),
+                status == 429 || status >= 500,
+            )
+        };
+
+        delivery.log(log, attempt, &outcome);
+        if !retry || attempt == MAX_ATTEMPTS {
+            return;
+        }
+        if !sleep_unless_stopping(backoff, stopping) {
+            delivery.log(log, attempt + 1, "dropped on quitting");
+            return;
+        }
+        backoff *= 2;
+    }
+}
+
+/// Sleeps for `duration`, or less if `stopping` is set meanwhile. Returns
+/// whether it slept the whole time.
+fn sleep_unless_stopping(duration: Duration, stopping: &AtomicBool) -> bool {
+    let deadline = Instant::now() + duration;
+    while !stopping.load(Ordering::Relaxed) {
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        thread::sleep((deadline - now).min(Duration::from_millis(100)));
+    }
+    false
+}
+
+fn write_log(log: Option<&PathBuf>, line: &str) {
+    let path = match log {
+        Some(path) => path,
+        None => return,
+    };
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    // Nowhere to report a failure to log to; the delivery went ahead anyway.
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+        let _ = writeln!(file, "{}", line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpListener;
+
+    fn thread(starter: Option<&str>) -> ThwDatum {
+        let mut datum = ThwDatum::bare("<Spell> & co", "Triggers & Scripts", "threads/a.1/");
+        datum.starter = starter.map(String::from);
+        datum
+    }
+
+    #[test]
+    fn discord_payloads() {
+        let url = "https://x.org/threads/a.1/";
+        let value = payload(Payload::Discord, &thread(Some("Bob")), url);
+        assert_eq!(
+            value,
+            json!({
+                "username": "THW Subscriber",
+                "embeds": [{
+                    "title": "<Spell> & co",
+                    "url": url,
+                    "description": "Triggers & Scripts",
+                    "author": { "name": "Bob" },
+                }],
+            })
+        );
+        let value = payload(Payload::Discord, &thread(None), url);
+        assert!(value["embeds"][0].get("author").is_none());
+    }
+
+    #[test]
+    fn slack_payloads_are_escaped() {
+        let value = payload(Payload::Slack, &thread(None), "https://x.org/");
+        assert_eq!(
+            value,
+            json!({ "text": "<https://x.org/|&lt;Spell&gt; &amp; co> in Triggers &amp; Scripts" })
+        );
+    }
+
+    #[test]
+    fn json_payloads_are_the_thread_and_its_url() {
+        let value = payload(Payload::Json, &thread(Some("Bob")), "https://x.org/");
+        assert_eq!(value["title"], "<Spell> & co");
+        assert_eq!(value["starter"], "Bob");
+        assert_eq!(value["thread_id"], 1);
+        assert_eq!(value["url"], "https://x.org/");
+    }
+
+    /// Answers one request per response in turn, and hands back the bodies
+    /// it was sent.
+    fn serve(responses: Vec<&'static str>) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let mut bodies = vec![];
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let lower = line.to_lowercase();
+                    if let Some(value) = lower.strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                bodies.push(String::from_utf8(body).unwrap());
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+            bodies
+        });
+        (url, handle)
+    }
+
+    fn delivery(url: String) -> Delivery {
+        Delivery {
+            name: "test".into(),
+            url,
+            body: "{}".into(),
+            thread: "https://x.org/threads/a.1/".into(),
+        }
+    }
+
+    fn log_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("thw-webhooks-{}-{}.log", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn outcomes(log: &PathBuf) -> Vec<String> {
+        let text = fs::read_to_string(log).unwrap();
+        let _ = fs::remove_file(log);
+        text.lines()
+            .map(|line| line.splitn(3, ' ').nth(2).unwrap().to_string())
+            .collect()
+    }
+
+    const UNAVAILABLE: &str =
+        "HTTP/1.1 503 Service Unavailable\r\nRetry-After: 1\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+    const NOT_FOUND: &str =
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+    const OK: &str = "HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n";
+
+    #[test]
+    fn retries_server_errors() {
+        let (url, server) = serve(vec![UNAVAILABLE, OK]);
+        let log = log_path("retry");
+        deliver(&delivery(url), Some(&log), &AtomicBool::new(false));
+
+        assert_eq!(server.join().unwrap(), ["{}", "{}"]);
+        assert_eq!(
+            outcomes(&log),
+            [
+                "attempt 1/4 https://x.org/threads/a.1/ failed: 503 Service Unavailable",
+                "attempt 2/4 https://x.org/threads/a.1/ delivered: 204",
+            ]
+        );
+    }
+
+    #[test]
+    fn gives_up_on_refused_deliveries() {
+        let (url, server) = serve(vec![NOT_FOUND]);
+        let log = log_path("refused");
+        deliver(&delivery(url), Some(&log), &AtomicBool::new(false));
+
+        assert_eq!(server.join().unwrap().len(), 1);
+        assert_eq!(
+            outcomes(&log),
+            ["attempt 1/4 https://x.org/threads/a.1/ failed: 404 Not Found"]
+        );
+    }
+
+    #[test]
+    fn stops_retrying_when_quitting() {
+        let (url, server) = serve(vec![UNAVAILABLE]);
+        let log = log_path("quitting");
+        deliver(&delivery(url), Some(&log), &AtomicBool::new(true));
+
+        server.join().unwrap();
+        assert_eq!(
+            outcomes(&log),
+            [
+                "attempt 1/4 https://x.org/threads/a.1/ failed: 503 Service Unavailable",
+                "attempt 2/4 https://x.org/threads/a.1/ dropped on quitting",
+            ]
+        );
+    }
+}
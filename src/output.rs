@@ -0,0 +1,125 @@
+use crate::demo::ThwDatum;
+use crate::feed;
+
+use serde::Serialize;
+
+use std::error::Error;
+use std::io::Write;
+use std::str::FromStr;
+
+/// How threads are printed outside of the terminal UI.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Format, String> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            _ => Err(format!(
+                "unknown format {:?}, expected text, json or csv",
+                s
+            )),
+        }
+    }
+}
+
+/// One thread as printed in JSON.
+#[derive(Serialize)]
+struct Line<'a> {
+    title: &'a str,
+    forum: &'a str,
+    url: String,
+}
+
+impl<'a> Line<'a> {
+    fn new(datum: &'a ThwDatum, base_url: &str) -> Line<'a> {
+        Line {
+            title: &datum.title,
+            forum: &datum.forum,
+            url: feed::url(base_url, &datum.href),
+        }
+    }
+}
+
+/// Writes the header that has to precede a stream of lines, if any.
+pub fn write_header(out: &mut impl Write, format: Format) -> Result<(), Box<dyn Error>> {
+    if format == Format::Csv {
+        writeln!(out, "forum,title,url")?;
+    }
+    Ok(())
+}
+
+/// Writes `datum` as a single line; JSON comes out as NDJSON.
+pub fn write_line(
+    out: &mut impl Write,
+    format: Format,
+    datum: &ThwDatum,
+    base_url: &str,
+) -> Result<(), Box<dyn Error>> {
+    let line = Line::new(datum, base_url);
+    match format {
+        Format::Text => writeln!(out, "{} :: {} :: {}", line.forum, line.title, line.url)?,
+        Format::Json => writeln!(out, "{}", serde_json::to_string(&line)?)?,
+        Format::Csv => writeln!(
+            out,
+            "{},{},{}",
+            csv_field(line.forum),
+            csv_field(line.title),
+            csv_field(&line.url)
+        )?,
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Writes all of `data` as one document; JSON comes out as a single array.
+pub fn write_all<'a>(
+    out: &mut impl Write,
+    format: Format,
+    data: impl IntoIterator<Item = &'a ThwDatum>,
+    base_url: &str,
+) -> Result<(), Box<dyn Error>> {
+    if format == Format::Json {
+        let lines: Vec<Line> = data
+            .into_iter()
+            .map(|datum| Line::new(datum, base_url))
+            .collect();
+        writeln!(out, "{}", serde_json::to_string_pretty(&lines)?)?;
+        return Ok(());
+    }
+
+    write_header(out, format)?;
+    for datum in data {
+        write_line(out, format, datum, base_url)?;
+    }
+    Ok(())
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_csv_fields_only_when_needed() {
+        assert_eq!(csv_field("A map"), "A map");
+        assert_eq!(csv_field("Triggers, Scripts"), "\"Triggers, Scripts\"");
+        assert_eq!(csv_field("A \"map\""), "\"A \"\"map\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+}
@@ -0,0 +1,110 @@
+use crate::demo::ThwDatum;
+
+use serde::{Deserialize, Serialize};
+
+use std::cmp::Reverse;
+
+/// How the topic list is ordered.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    NewestFirst,
+    NewestLast,
+    /// Alphabetically by forum, newest first within each.
+    Forum,
+    Title,
+    /// Busiest threads first.
+    Replies,
+}
+
+impl SortOrder {
+    /// The order after this one, wrapping around.
+    pub fn next(self) -> SortOrder {
+        match self {
+            SortOrder::NewestFirst => SortOrder::NewestLast,
+            SortOrder::NewestLast => SortOrder::Forum,
+            SortOrder::Forum => SortOrder::Title,
+            SortOrder::Title => SortOrder::Replies,
+            SortOrder::Replies => SortOrder::NewestFirst,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortOrder::NewestFirst => "newest first",
+            SortOrder::NewestLast => "newest last",
+            SortOrder::Forum => "by forum",
+            SortOrder::Title => "by title",
+            SortOrder::Replies => "by replies",
+        }
+    }
+
+    /// Sorts `threads`, which are given oldest first.
+    pub fn sort(self, threads: &mut Vec<&ThwDatum>) {
+        if self == SortOrder::NewestLast {
+            return;
+        }
+        // The sorts are stable, so ties stay newest first.
+        threads.reverse();
+        match self {
+            SortOrder::NewestFirst | SortOrder::NewestLast => {}
+            SortOrder::Forum => threads.sort_by_cached_key(|datum| datum.forum.to_lowercase()),
+            SortOrder::Title => threads.sort_by_cached_key(|datum| datum.title.to_lowercase()),
+            SortOrder::Replies => threads.sort_by_key(|datum| Reverse(datum.replies)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thread(id: u64, forum: &str, title: &str, replies: Option<u64>) -> ThwDatum {
+        let mut datum = ThwDatum::bare(title, forum, &format!("threads/a.{}/", id));
+        datum.replies = replies;
+        datum
+    }
+
+    fn sorted(order: SortOrder, threads: &[ThwDatum]) -> Vec<u64> {
+        let mut threads: Vec<&ThwDatum> = threads.iter().collect();
+        order.sort(&mut threads);
+        threads.iter().filter_map(|datum| datum.thread_id).collect()
+    }
+
+    /// Oldest first, the way the topic list keeps them.
+    fn threads() -> Vec<ThwDatum> {
+        vec![
+            thread(1, "Maps", "b", Some(2)),
+            thread(2, "triggers", "A", None),
+            thread(3, "Maps", "a", Some(2)),
+            thread(4, "Icons", "c", Some(7)),
+        ]
+    }
+
+    #[test]
+    fn newest_first_or_last() {
+        assert_eq!(sorted(SortOrder::NewestFirst, &threads()), [4, 3, 2, 1]);
+        assert_eq!(sorted(SortOrder::NewestLast, &threads()), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn ties_stay_newest_first() {
+        assert_eq!(sorted(SortOrder::Forum, &threads()), [4, 3, 1, 2]);
+        assert_eq!(sorted(SortOrder::Title, &threads()), [3, 2, 1, 4]);
+    }
+
+    #[test]
+    fn unknown_replies_go_last() {
+        assert_eq!(sorted(SortOrder::Replies, &threads()), [4, 3, 1, 2]);
+    }
+
+    #[test]
+    fn cycles_through_every_order() {
+        let mut order = SortOrder::default();
+        for _ in 0..5 {
+            order = order.next();
+        }
+        assert_eq!(order, SortOrder::default());
+    }
+}